@@ -0,0 +1,69 @@
+#!/usr/bin/env python3
+"""Writes src/unicode_tables.rs from the Unicode database bundled with Python.
+
+Usage: python3 scripts/unicode_tables.py > src/unicode_tables.rs
+"""
+import sys
+import unicodedata
+
+# Surrogates (Cs) are left out: they are not chars and have no UTF-8 encoding.
+CATEGORIES = [
+    "Cc", "Cf", "Cn", "Co",
+    "Ll", "Lm", "Lo", "Lt", "Lu",
+    "Mc", "Me", "Mn",
+    "Nd", "Nl", "No",
+    "Pc", "Pd", "Pe", "Pf", "Pi", "Po", "Ps",
+    "Sc", "Sk", "Sm", "So",
+    "Zl", "Zp", "Zs",
+]
+
+
+def ranges(pred):
+    out = []
+    for c in range(0x110000):
+        if not pred(c):
+            continue
+        if out and out[-1][1] == c - 1:
+            out[-1][1] = c
+        else:
+            out.append([c, c])
+    return out
+
+
+# Python identifiers follow UAX #31, except that '_' may start one.
+def xid_start(c):
+    return c != 0x5F and chr(c).isidentifier()
+
+
+def xid_continue(c):
+    return ("a" + chr(c)).isidentifier()
+
+
+def table(name, rs, out):
+    out.write(f"const {name}: &[(char, char)] = &[\n")
+    for i in range(0, len(rs), 4):
+        row = ", ".join(f"('\\u{{{lo:X}}}', '\\u{{{hi:X}}}')" for lo, hi in rs[i:i + 4])
+        out.write(f"    {row},\n")
+    out.write("];\n\n")
+
+
+def main(out):
+    out.write(f"// Generated by scripts/unicode_tables.py from Unicode "
+              f"{unicodedata.unidata_version}. Do not edit.\n\n")
+    props = []
+    for cat in CATEGORIES:
+        name = cat.upper()
+        table(name, ranges(lambda c: unicodedata.category(chr(c)) == cat), out)
+        props.append((cat, name))
+    table("XID_START", ranges(xid_start), out)
+    table("XID_CONTINUE", ranges(xid_continue), out)
+    props += [("XID_Start", "XID_START"), ("XID_Continue", "XID_CONTINUE")]
+
+    out.write("pub(crate) const PROPERTIES: &[(&str, &[(char, char)])] = &[\n")
+    for prop, name in props:
+        out.write(f"    (\"{prop}\", {name}),\n")
+    out.write("];\n")
+
+
+if __name__ == "__main__":
+    main(sys.stdout)
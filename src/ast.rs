@@ -1,42 +1,83 @@
-use crate::lexer::Op;
+use std::fmt;
+use crate::lexer::{Op, Span};
+use crate::unicode::Property;
 
+#[derive(Debug, PartialEq, Eq)]
 pub enum Node {
     Char(char),
+    Class(Vec<(char, char)>),
+    Property(Property),
     BinaryExpr(BinaryExprNode),
     UnaryExpr(UnaryExprNode),
+    Capture(CaptureNode),
 }
 
+#[derive(Debug, PartialEq, Eq)]
 pub struct BinaryExprNode {
     pub left:  Box<Node>,
     pub right: Box<Node>,
     pub op: Op,
 }
 
+#[derive(Debug, PartialEq, Eq)]
 pub struct UnaryExprNode {
     pub child: Box<Node>,
     pub op: Op,
 }
 
+/// A named group, `(?<name>child)`, whose span is reported with the token.
+#[derive(Debug, PartialEq, Eq)]
+pub struct CaptureNode {
+    pub name:  String,
+    pub child: Box<Node>,
+}
+
+/// A rule of the spec. When several rules match the same longest
+/// prefix, the one with the highest `priority` wins, and of those the
+/// one declared first.
+#[derive(Debug)]
 pub struct Match {
     pub root: Node,
-    pub name: String
+    pub name: String,
+    pub priority: i32,
+    pub span: Span
+}
+
+/// Indices of `matches` from the rule that wins ties to the one that
+/// loses them. Automata number their accepting rules in this order, so
+/// the lowest accept id wins.
+pub fn priority_order(matches: &[Match]) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..matches.len()).collect();
+    order.sort_by_key(|i| std::cmp::Reverse(matches[*i].priority));
+    return order;
 }
 impl Node {
     pub fn char(&self) -> char {
         if let Node::Char(c) = *self { return c; }
         panic!("Not A Letter!");
     }
+    /// Names of the capture groups, in the order they open.
+    pub fn groups(&self) -> Vec<&str> {
+        return match self {
+            Node::Char(_) | Node::Class(_) | Node::Property(_) => Vec::new(),
+            Node::BinaryExpr(n) => {
+                let mut names = n.left.groups();
+                names.extend(n.right.groups());
+                names
+            },
+            Node::UnaryExpr(n) => n.child.groups(),
+            Node::Capture(n) => {
+                let mut names = vec![n.name.as_str()];
+                names.extend(n.child.groups());
+                names
+            }
+        };
+    }
     pub fn print(&self) {
-        print!("{}", self.to_string());
+        print!("{}", self);
     }
 
-    pub fn to_string(&self) -> String {
-        let mut out = String::new();
-        self._print(&self, 0, &mut out);
-        return out;
-    } 
-
-    /* OUTPUT TO XML? */
+    // Debugging dump; see export.rs for real JSON and XML.
     fn _print(&self, node: &Node, depth: u32, out: &mut String) {
         let mut tabs = String::new();
         for _ in 0..depth { tabs.push_str("  "); }
@@ -52,6 +93,28 @@ impl Node {
                 self._print(&n.child, depth+1, out);
                 out.push_str(&format!("{tabs}</{:?}>\n", n.op));
             },
+            Node::Capture(n) => {
+                out.push_str(&format!("{tabs}<CAPTURE {}>\n", n.name));
+                self._print(&n.child, depth+1, out);
+                out.push_str(&format!("{tabs}</CAPTURE>\n"));
+            },
+            Node::Class(ranges) => {
+                out.push_str(&format!("{tabs}<CLASS>"));
+                for (lo, hi) in ranges {
+                    if lo == hi {
+                        out.push_str(&format!(" \"{}\"", lo.escape_debug()));
+                    } else {
+                        out.push_str(&format!(" \"{}\"-\"{}\"",
+                            lo.escape_debug(), hi.escape_debug()
+                        ));
+                    }
+                }
+                out.push_str(" </CLASS>\n");
+            },
+            Node::Property(p) => {
+                let esc = if p.negated { 'P' } else { 'p' };
+                out.push_str(&format!("{tabs}<PROPERTY> \\{esc}{{{}}} </PROPERTY>\n", p.name()));
+            },
             Node::Char(c) => {
                 out.push_str(&format!(
                     "{tabs}<\"{}\"> </\"{}\">\n",
@@ -60,4 +123,12 @@ impl Node {
             }
         }
     }
-}
\ No newline at end of file
+}
+
+impl fmt::Display for Node {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut out = String::new();
+        self._print(self, 0, &mut out);
+        return write!(f, "{}", out);
+    }
+}
@@ -0,0 +1,428 @@
+use std::{collections::HashMap, error::Error, time::Instant};
+use crate::ast::{self, Match};
+use crate::dfa::{DFA, ALPHABET, NULL, Limit, LimitError, Limits};
+use crate::lexer::Op;
+use crate::nfa::{self, Encoding, BuildError};
+use crate::utf8;
+
+/// A set of byte values.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ByteSet([u128; 2]);
+
+impl ByteSet {
+    pub const EMPTY: ByteSet = ByteSet([0; 2]);
+    pub const FULL: ByteSet = ByteSet([u128::MAX; 2]);
+
+    pub fn range(lo: u8, hi: u8) -> Self {
+        let mut set = ByteSet::EMPTY;
+        for b in lo..=hi { set.0[b as usize / 128] |= 1 << (b % 128); }
+        return set;
+    }
+
+    pub fn contains(&self, b: u8) -> bool {
+        return self.0[b as usize / 128] & (1 << (b % 128)) != 0;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return *self == ByteSet::EMPTY;
+    }
+
+    fn union(&self, other: &ByteSet) -> ByteSet {
+        return ByteSet([self.0[0] | other.0[0], self.0[1] | other.0[1]]);
+    }
+
+    fn intersect(&self, other: &ByteSet) -> ByteSet {
+        return ByteSet([self.0[0] & other.0[0], self.0[1] & other.0[1]]);
+    }
+
+    fn minus(&self, other: &ByteSet) -> ByteSet {
+        return ByteSet([self.0[0] & !other.0[0], self.0[1] & !other.0[1]]);
+    }
+
+    fn first(&self) -> Option<u8> {
+        return (0..=u8::MAX).find(|b| self.contains(*b));
+    }
+}
+
+/// A byte-level regex, kept in canonical form by its constructors so
+/// that equal languages usually get equal terms: alternations and
+/// intersections are flattened, sorted and deduplicated, concatenation
+/// associates to the right, and the usual identities are applied.
+/// Two regexes are one DFA state exactly when their terms are equal.
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Re {
+    /// Matches nothing.
+    Empty,
+    /// Matches the empty string.
+    Eps,
+    Bytes(ByteSet),
+    Cat(Box<Re>, Box<Re>),
+    Star(Box<Re>),
+    Or(Vec<Re>),
+    And(Vec<Re>),
+    Not(Box<Re>)
+}
+
+impl Re {
+    pub fn bytes(set: ByteSet) -> Re {
+        if set.is_empty() { return Re::Empty; }
+        return Re::Bytes(set);
+    }
+
+    pub fn cat(a: Re, b: Re) -> Re {
+        return match (a, b) {
+            (Re::Empty, _) | (_, Re::Empty) => Re::Empty,
+            (Re::Eps, b) => b,
+            (a, Re::Eps) => a,
+            (Re::Cat(x, y), b) => Re::cat(*x, Re::cat(*y, b)),
+            (a, b) => Re::Cat(Box::new(a), Box::new(b))
+        };
+    }
+
+    pub fn star(a: Re) -> Re {
+        return match a {
+            Re::Empty | Re::Eps => Re::Eps,
+            Re::Star(_) => a,
+            a => Re::Star(Box::new(a))
+        };
+    }
+
+    pub fn complement(a: Re) -> Re {
+        return match a {
+            Re::Not(a) => *a,
+            a => Re::Not(Box::new(a))
+        };
+    }
+
+    pub fn or(alts: Vec<Re>) -> Re {
+        let mut flat: Vec<Re> = Vec::new();
+        let mut bytes = ByteSet::EMPTY;
+        for alt in alts {
+            match alt {
+                Re::Empty => {},
+                Re::Or(inner) => flat.extend(inner),
+                Re::Bytes(set) => bytes = bytes.union(&set),
+                alt => flat.push(alt)
+            }
+        }
+        // Or flattened above may hold byte sets of its own.
+        flat.retain(|alt| match alt {
+            Re::Bytes(set) => { bytes = bytes.union(set); false },
+            _ => true
+        });
+        if flat.contains(&Re::universal()) { return Re::universal(); }
+        if !bytes.is_empty() { flat.push(Re::Bytes(bytes)); }
+        return Re::set(flat, Re::Or, Re::Empty);
+    }
+
+    pub fn and(parts: Vec<Re>) -> Re {
+        let mut flat: Vec<Re> = Vec::new();
+        for part in parts {
+            match part {
+                Re::And(inner) => flat.extend(inner),
+                part => flat.push(part)
+            }
+        }
+        if flat.contains(&Re::Empty) { return Re::Empty; }
+        flat.retain(|part| *part != Re::universal());
+        return Re::set(flat, Re::And, Re::universal());
+    }
+
+    /// Matches every string.
+    pub fn universal() -> Re {
+        return Re::Not(Box::new(Re::Empty));
+    }
+
+    fn set(mut items: Vec<Re>, make: fn(Vec<Re>) -> Re, none: Re) -> Re {
+        items.sort();
+        items.dedup();
+        return match items.len() {
+            0 => none,
+            1 => items.pop().unwrap(),
+            _ => make(items)
+        };
+    }
+
+    pub fn nullable(&self) -> bool {
+        return match self {
+            Re::Empty | Re::Bytes(_) => false,
+            Re::Eps | Re::Star(_) => true,
+            Re::Cat(a, b) => a.nullable() && b.nullable(),
+            Re::Or(alts) => alts.iter().any(Re::nullable),
+            Re::And(parts) => parts.iter().all(Re::nullable),
+            Re::Not(a) => !a.nullable()
+        };
+    }
+
+    /// The strings `w` such that `bw` matches this regex.
+    pub fn derive(&self, b: u8) -> Re {
+        return match self {
+            Re::Empty | Re::Eps => Re::Empty,
+            Re::Bytes(set) => if set.contains(b) { Re::Eps } else { Re::Empty },
+            Re::Cat(x, y) => {
+                let head = Re::cat(x.derive(b), (**y).clone());
+                if !x.nullable() { return head; }
+                Re::or(vec![head, y.derive(b)])
+            },
+            Re::Star(x) => Re::cat(x.derive(b), self.clone()),
+            Re::Or(alts) => Re::or(alts.iter().map(|a| a.derive(b)).collect()),
+            Re::And(parts) => Re::and(parts.iter().map(|p| p.derive(b)).collect()),
+            Re::Not(x) => Re::complement(x.derive(b))
+        };
+    }
+
+    // Byte sets that may be read first. Bytes they do not tell apart
+    // have the same derivative.
+    fn heads(&self, out: &mut Vec<ByteSet>) {
+        match self {
+            Re::Empty | Re::Eps => {},
+            Re::Bytes(set) => out.push(*set),
+            Re::Cat(x, y) => {
+                x.heads(out);
+                if x.nullable() { y.heads(out); }
+            },
+            Re::Star(x) | Re::Not(x) => x.heads(out),
+            Re::Or(items) | Re::And(items) => {
+                for item in items { item.heads(out); }
+            }
+        }
+    }
+
+    /// Lowers `node`, the root of match `rule`, to bytes, reading
+    /// characters as `encoding` does.
+    pub fn from_node(node: &ast::Node, encoding: Encoding, rule: usize) -> Result<Re, BuildError> {
+        return match node {
+            ast::Node::Char(c) => Re::class(&[(*c, *c)], encoding, rule),
+            ast::Node::Class(ranges) => Re::class(ranges, encoding, rule),
+            ast::Node::Property(p) => Re::class(&nfa::property_ranges(*p, encoding), encoding, rule),
+            ast::Node::Capture(n) => Re::from_node(&n.child, encoding, rule),
+            ast::Node::BinaryExpr(n) => {
+                let left = || Re::from_node(&n.left, encoding, rule);
+                let right = || Re::from_node(&n.right, encoding, rule);
+                match (n.op, &*n.left, &*n.right) {
+                    (Op::DASH, ast::Node::Char(lo), ast::Node::Char(hi)) =>
+                        Re::class(&[(*lo, *hi)], encoding, rule),
+                    (Op::DASH, _, _) => Err(BuildError::InvalidRange { rule }),
+                    (Op::BAR, _, _) => Ok(Re::or(vec![left()?, right()?])),
+                    (Op::AND, _, _) => Ok(Re::cat(left()?, right()?)),
+                    (op, _, _) => Err(BuildError::UnexpectedOp { rule, op })
+                }
+            },
+            ast::Node::UnaryExpr(n) => {
+                let child = Re::from_node(&n.child, encoding, rule)?;
+                match n.op {
+                    Op::STAR => Ok(Re::star(child)),
+                    Op::PLUS => Ok(Re::cat(child.clone(), Re::star(child))),
+                    Op::QUESTION => Ok(Re::or(vec![child, Re::Eps])),
+                    op => Err(BuildError::UnexpectedOp { rule, op })
+                }
+            }
+        };
+    }
+
+    fn class(ranges: &[(char, char)], encoding: Encoding, rule: usize) -> Result<Re, BuildError> {
+        let byte = |c: char| u8::try_from(c).map_err(|_| BuildError::OutOfRange { rule, c });
+        let seqs: Vec<Vec<(u8, u8)>> = match encoding {
+            Encoding::Utf8 => ranges.iter()
+                .flat_map(|(lo, hi)| utf8::sequences(*lo, *hi))
+                .collect(),
+            Encoding::Latin1 => ranges.iter()
+                .filter(|(lo, hi)| lo <= hi)
+                .map(|(lo, hi)| Ok(vec![(byte(*lo)?, byte(*hi)?)]))
+                .collect::<Result<_, BuildError>>()?
+        };
+        return Ok(Re::sequences(&seqs));
+    }
+
+    // Sequences starting with the same range share it, like a trie.
+    fn sequences(seqs: &[Vec<(u8, u8)>]) -> Re {
+        let mut alts: Vec<Re> = Vec::new();
+        let mut i = 0;
+        while i < seqs.len() {
+            let (lo, hi) = seqs[i][0];
+            let mut j = i;
+            while j < seqs.len() && seqs[j][0] == (lo, hi) { j += 1; }
+            let rest: Vec<Vec<(u8, u8)>> = seqs[i..j].iter()
+                .filter(|seq| seq.len() > 1)
+                .map(|seq| seq[1..].to_vec())
+                .collect();
+            let tail = if rest.is_empty() { Re::Eps } else { Re::sequences(&rest) };
+            alts.push(Re::cat(Re::bytes(ByteSet::range(lo, hi)), tail));
+            i = j;
+        }
+        return Re::or(alts);
+    }
+}
+
+/// Builds a DFA from the rules by taking derivatives. A state is the
+/// tuple of what is left of the whitespace, `WS*` at the start, and of
+/// each rule, `WS* r_i` at the start, and reading a byte derives every
+/// component. The state accepts the first rule, in priority order,
+/// whose component is nullable.
+pub fn build_from_matches(matches: &[Match], encoding: Encoding) -> Result<DFA, BuildError> {
+    let (rules, ws) = split(matches, encoding)?;
+    return Ok(build(rules, ws));
+}
+
+/// `build_from_matches`, giving up once it goes over the DFA state or
+/// time limit.
+pub fn build_from_matches_with(matches: &[Match], encoding: Encoding, limits: &Limits)
+    -> Result<DFA, Box<dyn Error>> {
+    let (rules, ws) = split(matches, encoding)?;
+    return Ok(build_with(rules, ws, limits)?);
+}
+
+/// Builds a DFA over `rules`, in priority order, skipping any `ws`
+/// before a token.
+pub fn build(rules: Vec<(String, Re)>, ws: Re) -> DFA {
+    return build_with(rules, ws, &Limits::none()).expect("Unlimited construction never fails");
+}
+
+// The named rules, in priority order, and the whitespace rules
+// alternated.
+fn split(matches: &[Match], encoding: Encoding) -> Result<(Vec<(String, Re)>, Re), BuildError> {
+    let mut ws: Vec<Re> = Vec::new();
+    let mut rules: Vec<(String, Re)> = Vec::new();
+    for rule in ast::priority_order(matches) {
+        let m = &matches[rule];
+        let re = Re::from_node(&m.root, encoding, rule)?;
+        if m.name.is_empty() {
+            ws.push(re);
+        } else {
+            rules.push((m.name.clone(), re));
+        }
+    }
+    return Ok((rules, Re::or(ws)));
+}
+
+/// `build`, giving up once it goes over the DFA state or time limit.
+pub fn build_with(rules: Vec<(String, Re)>, ws: Re, limits: &Limits) -> Result<DFA, LimitError> {
+    let begin = Instant::now();
+    let mut dfa = DFA::new();
+    let skip = Re::star(ws);
+    // With the whitespace in the tuple, only whitespace read from the
+    // start leads back to it, and the start state is never the dead
+    // state, even without rules.
+    let mut start: Vec<Re> = vec![skip.clone()];
+    start.extend(rules.iter().map(|(_, re)| Re::cat(skip.clone(), re.clone())));
+    dfa.labels = rules.into_iter().map(|(name, _)| name).collect();
+
+    let mut d_states: Vec<Vec<Re>> = Vec::new();
+    let mut seen: HashMap<Vec<Re>, usize> = HashMap::new();
+    state(start, &mut dfa, &mut d_states, &mut seen);
+    // The dead state, which is there even if no input reaches it.
+    dfa.dead = state(vec![Re::Empty; dfa.labels.len() + 1], &mut dfa, &mut d_states, &mut seen);
+    let mut index = 0;
+    while index < d_states.len() {
+        if dfa.ncount > limits.dfa_states {
+            return Err(LimitError { limit: Limit::DfaStates(limits.dfa_states), rules: Vec::new() });
+        }
+        if begin.elapsed() > limits.time {
+            return Err(LimitError { limit: Limit::Time(limits.time), rules: Vec::new() });
+        }
+        for part in partition(&d_states[index]) {
+            let b = part.first().expect("Parts are non-empty");
+            let next: Vec<Re> = d_states[index].iter().map(|re| re.derive(b)).collect();
+            let u = state(next, &mut dfa, &mut d_states, &mut seen);
+            for c in 0..ALPHABET {
+                if part.contains(c as u8) { dfa.jumps[index][c] = u; }
+            }
+        }
+        index += 1;
+    }
+    return Ok(dfa);
+}
+
+fn state(res: Vec<Re>, dfa: &mut DFA, d_states: &mut Vec<Vec<Re>>,
+    seen: &mut HashMap<Vec<Re>, usize>) -> usize {
+    if let Some(u) = seen.get(&res) { return *u; }
+    let accept = res[1..].iter()
+        .position(Re::nullable)
+        .map_or(0, |i| i + 1);
+    seen.insert(res.clone(), dfa.ncount);
+    d_states.push(res);
+    dfa.jumps.push(vec![NULL; ALPHABET]);
+    dfa.accepts.push(accept);
+    dfa.ncount += 1;
+    return dfa.ncount - 1;
+}
+
+// Splits the bytes into sets whose derivatives agree.
+fn partition(res: &[Re]) -> Vec<ByteSet> {
+    let mut heads: Vec<ByteSet> = Vec::new();
+    for re in res { re.heads(&mut heads); }
+    heads.sort();
+    heads.dedup();
+    let mut parts = vec![ByteSet::FULL];
+    for head in heads {
+        let mut split: Vec<ByteSet> = Vec::new();
+        for part in parts {
+            for piece in [part.intersect(&head), part.minus(&head)] {
+                if !piece.is_empty() { split.push(piece); }
+            }
+        }
+        parts = split;
+    }
+    return parts;
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{path::Path, fs::File, io::{BufReader, BufRead}};
+    use crate::{lexer::Lexer, parser::{Parser, parse_regex}};
+    use super::*;
+
+    fn re(src: &str) -> Re {
+        return Re::from_node(&parse_regex(src).expect("Valid regex"), Encoding::Utf8, 0)
+            .expect("Valid rule");
+    }
+
+    #[test]
+    fn test_matches() {
+        let path = "tests/data/regex/input";
+        let mut i = 0;
+        while Path::new(&format!("{path}/match-{i}.txt")).exists() {
+            let lexer = Lexer::new(&format!("{path}/match-{i}.txt")).expect("Invalid Path");
+            let mut parser = Parser::new(lexer).expect("File should be non-empty!");
+            let matches = parser.parse().expect("Invalid parse");
+            let dfa = DFA::compress(build_from_matches(&matches, Encoding::Utf8).unwrap());
+            for id in ["right", "wrong"] {
+                let file = File::open(format!("{path}/{id}-words-{i}.txt"))
+                    .expect("Should be valid...");
+                let reader = BufReader::new(file);
+                for word in reader.lines().map_while(Result::ok) {
+                    assert!((dfa.run(&word) != 0) == (id == "right"), "{word}");
+                }
+            }
+            i += 1;
+        }
+    }
+
+    #[test]
+    fn canonical() {
+        assert!(re("a|b") == re("b|a") && re("(a|b)|c") == re("a|(b|c)"));
+        assert!(re("a|a") == re("a") && re("[a-c]|d") == re("[a-d]"));
+        assert!(re("(ab)c") == re("a(bc)") && re("(a*)*") == re("a*"));
+        assert!(Re::and(vec![re("x"), Re::Empty]) == Re::Empty);
+        assert!(Re::complement(Re::complement(re("x"))) == re("x"));
+        assert!(re("ab").derive(b'a') == re("b") && re("ab").derive(b'b') == Re::Empty);
+    }
+
+    #[test]
+    fn intersection_and_complement() {
+        // Identifiers other than the keyword "if".
+        let ident = Re::and(vec![re("[a-z]+"), Re::complement(re("if"))]);
+        // Binary strings with no "00" in them.
+        let no_00 = Re::and(vec![
+            re("[0-1]*"),
+            Re::complement(Re::cat(Re::universal(), Re::cat(re("00"), Re::universal())))
+        ]);
+        let dfa = build(vec![
+            ("IDENT".to_string(), ident),
+            ("NO_00".to_string(), no_00)
+        ], Re::Empty);
+        assert!(dfa.run("i") == 1 && dfa.run("iff") == 1 && dfa.run("if") == 0);
+        assert!(dfa.run("01") == 2 && dfa.run("00") == 0 && dfa.run("1001") == 0);
+        assert!(dfa.run("010") == 2 && dfa.run("") == 2 && dfa.run("-") == 0);
+    }
+}
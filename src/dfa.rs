@@ -1,39 +1,232 @@
+use std::{collections::HashMap, fmt, time::{Duration, Instant}};
 use crate::nfa::{NFA};
 pub(crate) const NULL: usize = usize::MAX;
+/// Automata read bytes, so every state has a transition per byte value.
+pub const ALPHABET: usize = u8::MAX as usize + 1;
+
+/// Bytes that every transition treats alike share a class, so a DFA
+/// only needs a column per class. Classes are runs of consecutive
+/// bytes, numbered in byte order.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ByteClasses {
+    map: [u8; ALPHABET],
+    count: usize
+}
+
+impl ByteClasses {
+    /// One class per byte.
+    pub fn identity() -> Self {
+        return ByteClasses { map: std::array::from_fn(|b| b as u8), count: ALPHABET };
+    }
+
+    /// The coarsest classes that never split one of `ranges`.
+    pub fn from_ranges(ranges: impl IntoIterator<Item = (u8, u8)>) -> Self {
+        // A class starts at each byte that starts or follows a range.
+        let mut starts = [false; ALPHABET + 1];
+        for (lo, hi) in ranges {
+            starts[lo as usize] = true;
+            starts[hi as usize + 1] = true;
+        }
+        let mut map = [0; ALPHABET];
+        let mut count = 0;
+        for (b, class) in map.iter_mut().enumerate() {
+            if b > 0 && starts[b] { count += 1; }
+            *class = count as u8;
+        }
+        return ByteClasses { map, count: count + 1 };
+    }
+
+    pub fn get(&self, b: u8) -> usize {
+        return self.map[b as usize] as usize;
+    }
+
+    pub fn len(&self) -> usize {
+        return self.count;
+    }
+
+    pub fn is_empty(&self) -> bool {
+        return self.count == 0;
+    }
+
+    /// The class of each byte, indexed by byte.
+    pub fn map(&self) -> &[u8; ALPHABET] {
+        return &self.map;
+    }
+}
+
+/// Bounds on building a DFA, so a spec whose DFA explodes fails with
+/// an error instead of exhausting memory. The direct and derivative
+/// constructions have no NFA, so `nfa_states` does not bound them.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Limits {
+    pub nfa_states: usize,
+    pub dfa_states: usize,
+    pub time: Duration
+}
+
+impl Default for Limits {
+    fn default() -> Self {
+        return Limits {
+            nfa_states: 1 << 20,
+            dfa_states: 1 << 17,
+            time: Duration::from_secs(60)
+        };
+    }
+}
+
+impl Limits {
+    pub fn none() -> Self {
+        return Limits { nfa_states: usize::MAX, dfa_states: usize::MAX, time: Duration::MAX };
+    }
+}
+
+/// The limit that was exceeded.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Limit {
+    NfaStates(usize),
+    DfaStates(usize),
+    Time(Duration)
+}
+
+/// A construction that went over one of its `Limits`. `rules` names
+/// the rules with the most NFA states in the DFA states built when it
+/// stopped, most first, with that count; it is empty for the
+/// constructions without an NFA.
+#[derive(Debug, PartialEq, Eq)]
+pub struct LimitError {
+    pub limit: Limit,
+    pub rules: Vec<(String, usize)>
+}
+impl fmt::Display for LimitError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.limit {
+            Limit::NfaStates(n) => write!(f, "NFA exceeded {n} states")?,
+            Limit::DfaStates(n) => write!(f, "DFA exceeded {n} states")?,
+            Limit::Time(t) => write!(f, "DFA construction exceeded {t:?}")?
+        }
+        let rules: Vec<String> = self.rules.iter()
+            .map(|(name, count)| format!("{name} ({count})"))
+            .collect();
+        if !rules.is_empty() { write!(f, "; most states come from {}", rules.join(", "))?; }
+        return Ok(());
+    }
+}
+impl std::error::Error for LimitError {}
+
+// How many rules a LimitError names.
+const BLAMED: usize = 3;
+
+/// `jumps[s][k]` is the state `s` moves to on a byte of class `k`.
+/// `dead` is the sink that accepts nothing, which every DFA has.
 pub struct DFA {
     pub ncount:  usize,
-    pub jumps:   Vec<[usize; u8::MAX as usize]>,
+    pub jumps:   Vec<Vec<usize>>,
+    pub classes: ByteClasses,
     pub accepts: Vec<usize>,
     pub labels:  Vec<String>,
     pub dead:    usize
 }
 
+impl Default for DFA {
+    fn default() -> Self {
+        return DFA::new();
+    }
+}
+
 #[allow(dead_code)]
 impl DFA {
     pub fn new() -> Self {
         return DFA {
             ncount:  0,
             jumps:   Vec::new(),
+            classes: ByteClasses::identity(),
             accepts: Vec::new(),
             labels:  Vec::new(),
             dead:    0
         };
     }
 
-    // TODO: Hycroft algorithm (guaranteed nlog(n))
-    // https://www.cs.cornell.edu/courses/cs2800/2013fa/Handouts/minimization.pdf
+    /// The state `state` moves to on byte `b`.
+    pub fn next(&self, state: usize, b: u8) -> usize {
+        return self.jumps[state][self.classes.get(b)];
+    }
+
+    /// The smallest DFA that keeps the start and dead states apart, by
+    /// Hopcroft's partition refinement in O(n log n). States start out
+    /// split by the rule they accept, so merged states keep their
+    /// labels, and the dead state and the start state start out alone,
+    /// so each stays a state of its own: the start is then only entered
+    /// again by whitespace. The result can thus have a state or two more
+    /// than the minimal DFA.
     pub fn compress(dfa: DFA) -> Self {
+        if dfa.ncount == 0 { return dfa; }
+        let n = dfa.ncount;
+        let k = dfa.classes.len();
+        // pre[start[c * n + t]..start[c * n + t + 1]] reach t on class c.
+        let mut start = vec![0; k * n + 1];
+        for row in &dfa.jumps {
+            for (c, t) in row.iter().enumerate() { start[c * n + t + 1] += 1; }
+        }
+        for i in 0..k * n { start[i + 1] += start[i]; }
+        let mut fill = start.clone();
+        let mut pre = vec![0; k * n];
+        for (s, row) in dfa.jumps.iter().enumerate() {
+            for (c, t) in row.iter().enumerate() {
+                pre[fill[c * n + t]] = s;
+                fill[c * n + t] += 1;
+            }
+        }
+
+        let mut part = Partition::new(&DFA::blocks(&dfa));
+        let largest = (0..part.len()).max_by_key(|b| part.size(*b)).unwrap_or(0);
+        let mut work: Vec<usize> = (0..part.len()).filter(|b| *b != largest).collect();
+        let mut in_work = vec![true; part.len()];
+        in_work[largest] = false;
+        let mut touched: Vec<usize> = Vec::new();
+        while let Some(splitter) = work.pop() {
+            in_work[splitter] = false;
+            let members = part.members(splitter).to_vec();
+            for c in 0..k {
+                for t in &members {
+                    for s in &pre[start[c * n + t]..start[c * n + t + 1]] {
+                        if part.mark(*s) { touched.push(part.block[*s]); }
+                    }
+                }
+                for b in touched.drain(..) {
+                    let Some(nb) = part.split(b) else { continue };
+                    in_work.push(false);
+                    if in_work[b] || part.size(nb) <= part.size(b) {
+                        work.push(nb);
+                        in_work[nb] = true;
+                    } else {
+                        work.push(b);
+                        in_work[b] = true;
+                    }
+                }
+            }
+        }
+        return DFA::merge(dfa, &part.block);
+    }
+
+    /// The same DFA as `compress`, by filling the table of
+    /// distinguishable pairs until nothing changes. O(n^2) per pass, so
+    /// only for checking `compress` against.
+    #[allow(clippy::needless_range_loop)]
+    pub fn compress_table(dfa: DFA) -> Self {
+        if dfa.ncount == 0 { return dfa; }
         let mut table = vec![vec![false; dfa.ncount]; dfa.ncount];
         for i in 0..table.len() {
             for j in 0..=i {
-                table[i][j] = dfa.accepts[i] != dfa.accepts[j];
+                table[i][j] = (i == dfa.dead) != (j == dfa.dead) ||
+                    (i == 0) != (j == 0) ||
+                    dfa.accepts[i] != dfa.accepts[j];
             }
         }
 
         let mut changed = true;
         while changed {
             changed = false;
-            for idx in 0..u8::MAX as usize {
+            for idx in 0..dfa.classes.len() {
                 for i in 0..table.len() {
                     for j in 0..=i {
                         if table[i][j] { continue; }
@@ -48,119 +241,152 @@ impl DFA {
                 }
             }
         }
+        let mut class: Vec<usize> = (0..dfa.ncount).collect();
+        for i in 0..table.len() {
+            if let Some(j) = (0..i).find(|j| !table[i][*j]) { class[i] = class[j]; }
+        }
+        return DFA::merge(dfa, &class);
+    }
+
+    // The initial block of each state: the rule it accepts, with the
+    // dead state and the start state apart.
+    fn blocks(dfa: &DFA) -> Vec<usize> {
+        return (0..dfa.ncount)
+            .map(|s| match s {
+                s if s == dfa.dead => NULL,
+                0 => NULL - 1,
+                s => dfa.accepts[s]
+            })
+            .collect();
+    }
+
+    // Merges the states in each class, numbering classes in order of
+    // their first state so the start state stays 0.
+    fn merge(dfa: DFA, class: &[usize]) -> Self {
         let mut reps: Vec<usize> = Vec::new();
+        let mut ids = vec![NULL; dfa.ncount];
         let mut id = vec![NULL; dfa.ncount];
-        for i in 0..table.len() {
-            for j in 0..i {
-                if !table[i][j] {
-                    id[i] = j;
-                    break;
-                }
+        for i in 0..dfa.ncount {
+            if ids[class[i]] == NULL {
+                ids[class[i]] = reps.len();
+                reps.push(i);
             }
-            if id[i] != NULL { continue; }
-            id[i] = reps.len();
-            reps.push(i);
+            id[i] = ids[class[i]];
         }
 
         let mut accepts = vec![0; reps.len()];
-        let mut jumps = vec![[NULL; u8::MAX as usize]; reps.len()];
+        let mut jumps = vec![Vec::new(); reps.len()];
         for rep in &reps {
-            for i in 0..u8::MAX as usize {
-                jumps[id[*rep]][i] = id[dfa.jumps[*rep][i]];
-            }
+            jumps[id[*rep]] = dfa.jumps[*rep].iter().map(|t| id[*t]).collect();
             accepts[id[*rep]] = dfa.accepts[*rep];
         }
         return Self {
             ncount: reps.len(),
             jumps,
+            classes: dfa.classes.clone(),
             accepts,
             labels: dfa.labels.clone(),
             dead: id[dfa.dead]
         };
     }
 
+    /// The DFA of `nfa`, with a column per byte class of its edges.
     pub fn subset_construction(nfa: NFA) -> Self {
-        let mut ncount:  usize = 1;
-        let mut jumps = vec![[NULL; u8::MAX as usize]; 1];
-        let mut accepts: Vec<usize> = vec![0; 1];
+        return DFA::subset_construction_with(nfa, &Limits::none())
+            .expect("Unlimited construction never fails");
+    }
+
+    /// `subset_construction`, giving up once it goes over the DFA state
+    /// or time limit. `NFA::build_with_limits` bounds the NFA states.
+    pub fn subset_construction_with(nfa: NFA, limits: &Limits) -> Result<Self, LimitError> {
+        let begin = Instant::now();
+        // Without epsilon edges every set of states is already closed.
+        let nfa = nfa.remove_eps();
+        let classes = nfa.byte_classes();
+        // The start state, then the dead state for the empty set, which
+        // is there even if no input reaches it.
+        let dead = 1;
+        let mut ncount:  usize = 2;
+        let mut jumps = vec![vec![NULL; classes.len()], vec![dead; classes.len()]];
+        let mut accepts: Vec<usize> = vec![nfa.accepts[0], 0];
         let mut unmarked = vec![0usize; 1];
-        let mut d_states: Vec<Vec<usize>> = vec![
-            DFA::eps_closure(&nfa, vec![0; 1]); 1
-        ];
-        let mut dead = NULL;
+        // Sets are kept sorted, so equal sets are equal vectors.
+        let mut d_states: HashMap<Vec<usize>, usize> = HashMap::from([
+            (vec![0], 0), (Vec::new(), dead)
+        ]);
+        let mut sets: Vec<Vec<usize>> = vec![vec![0], Vec::new()];
 
         while let Some(index) = unmarked.pop() {
-            //println!("index: {}", index);
-            for c in 0..u8::MAX {
-                // MOVE
-                let mut has = vec![false; nfa.ncount];
-                let mut nxt: Vec<usize> = Vec::new();
-                
-                for d in &d_states[index] {
-                    let mv = nfa.jumps[*d][c as usize];
-                    if mv == NULL || has[mv] { continue; }
-                    nxt.push(mv);
-                    has[mv] = true;
+            if ncount > limits.dfa_states {
+                return Err(DFA::blame(&nfa, &sets, Limit::DfaStates(limits.dfa_states)));
+            }
+            if begin.elapsed() > limits.time {
+                return Err(DFA::blame(&nfa, &sets, Limit::Time(limits.time)));
+            }
+            // MOVE
+            let mut moves: Vec<Vec<usize>> = vec![Vec::new(); classes.len()];
+            for d in &sets[index] {
+                for e in &nfa.jumps[*d] {
+                    for mv in &mut moves[classes.get(e.lo)..=classes.get(e.hi)] {
+                        mv.push(e.to);
+                    }
                 }
-
-                //if index == 6 { println!("Len: {}", nxt.len()); }
-                let state = DFA::eps_closure(&nfa,nxt);
+            }
+            for c in 0..classes.len() {
+                let mut state = std::mem::take(&mut moves[c]);
+                state.sort_unstable();
+                state.dedup();
 
                 // Seen Before?
-                let mut u = d_states.len();
-                for i in 0..d_states.len() {
-                    if d_states[i] == state {
-                        u = i;
-                        break;
+                let u = match d_states.get(&state) {
+                    Some(u) => *u,
+                    None => {
+                        let u = sets.len();
+                        accepts.push(DFA::is_accept(&nfa, &state));
+                        d_states.insert(state.clone(), u);
+                        sets.push(state);
+                        jumps.push(vec![NULL; classes.len()]);
+                        unmarked.push(u);
+                        ncount += 1;
+                        u
                     }
-                }
-                //println!("u: {}", u);
-                if u == d_states.len() {
-                    if state == Vec::new() { dead = u; }
-                    d_states.push(state.clone());
-                    jumps.push([NULL; u8::MAX as usize]);
-                    accepts.push(DFA::is_accept(&nfa, state));
-                    unmarked.push(u);
-                    ncount += 1;
-                }
-                jumps[index][c as usize] = u;
+                };
+                jumps[index][c] = u;
             }
         }
-        assert!(dead != NULL, "Dead state must exist!");
-        return Self {
+        return Ok(Self {
             ncount,
             jumps,
+            classes,
             accepts,
             labels: nfa.labels.clone(),
             dead
-        }
+        });
     }
 
-    fn eps_closure(nfa: &NFA, set: Vec<usize>) -> Vec<usize> {
-        let mut has = vec![false; nfa.ncount];
-        let mut closure: Vec<usize> = set;
-        let mut stack: Vec<usize> = Vec::new();
-        for s in &closure { 
-            has[*s] = true;
-            stack.push(*s); 
-        }
-        while let Some(s) = stack.pop() {
-            for nbr in &nfa.eps[s] {
-                if has[*nbr] { continue; }
-                has[*nbr] = true;
-                closure.push(*nbr);
-                stack.push(*nbr);
-            }
-        } 
-        return closure;
+    // Counts the states of each rule over all the sets. A rule that
+    // stays alive in every set adds one per set, while the rule that
+    // explodes adds many.
+    pub(crate) fn blame(nfa: &NFA, sets: &[Vec<usize>], limit: Limit) -> LimitError {
+        let mut counts = vec![0; nfa.labels.len() + 1];
+        for s in sets.iter().flatten() { counts[nfa.owners[*s]] += 1; }
+        let mut rules: Vec<(String, usize)> = (1..counts.len())
+            .filter(|r| counts[*r] > 0)
+            .map(|r| (nfa.labels[r - 1].clone(), counts[r]))
+            .collect();
+        rules.sort_by_key(|(_, count)| std::cmp::Reverse(*count));
+        rules.truncate(BLAMED);
+        return LimitError { limit, rules };
     }
 
-    fn is_accept(nfa: &NFA, set: Vec<usize>) -> usize {
-        for s in set {
-            let acc = nfa.accepts[s];
-            if acc != 0 { return acc; }
-        }
-        return 0;
+    // The rule that wins among those accepted by the set: rules are
+    // numbered in priority order, so the lowest.
+    fn is_accept(nfa: &NFA, set: &[usize]) -> usize {
+        return set.iter()
+            .map(|s| nfa.accepts[*s])
+            .filter(|acc| *acc != 0)
+            .min()
+            .unwrap_or(0);
     }
 
     #[cfg(debug_assertions)]
@@ -169,26 +395,24 @@ impl DFA {
         println!("digraph DFA {{");
         for state in 0..self.ncount {
             let mut ind = 0;
-            while ind < u8::MAX {
-                let nbr = self.jumps[state][ind as usize];
+            while ind < ALPHABET {
+                let nbr = self.next(state, ind as u8);
                 if nbr == NULL { ind += 1; continue };
 
                 let start = ind;
-                while ind + 1 < u8::MAX &&
-                    self.jumps[state][(ind + 1) as usize] == nbr {
+                while ind + 1 < ALPHABET &&
+                    self.next(state, ind as u8 + 1) == nbr {
                     ind += 1;
                 }
 
                 if start == ind {
                     println!("\t{} -> {} [label=\"{}\"];",
-                        state, nbr, (ind as char).escape_debug()
+                        state, nbr, (ind as u8 as char).escape_debug()
                     );
                 } else {
-                    println!("\t{} -> {} [label=\"{}\"];",
-                        state, nbr,
-                        format!("{}-{}", (start as char).escape_debug(), 
-                            (ind as char).escape_debug()
-                        )
+                    println!("\t{} -> {} [label=\"{}-{}\"];",
+                        state, nbr, (start as u8 as char).escape_debug(),
+                        (ind as u8 as char).escape_debug()
                     );
                 }
                 ind += 1;
@@ -198,45 +422,125 @@ impl DFA {
     }
 }
 
-#[cfg(test)]
-mod tests {
-    use std::{path::Path, fs::File, io::{BufReader, BufRead}};
-    use crate::{lexer::Lexer, parser::Parser, nfa::NFA};
-    use super::*;
+// A partition of the states into blocks. The members of each block are
+// contiguous in `elems`, with the marked ones first.
+struct Partition {
+    elems: Vec<usize>,
+    loc:   Vec<usize>,
+    block: Vec<usize>,
+    first: Vec<usize>,
+    mid:   Vec<usize>,
+    end:   Vec<usize>
+}
 
-    impl DFA {
-        fn accepts(&self, s: &str) -> bool {
-            let mut state = 0;
-            let chars = s.chars();
-            for c in chars {
-                let nxt = self.jumps[state][c as usize];
-                if nxt == NULL { return false; }
-                state = nxt;
+impl Partition {
+    // One block per distinct key.
+    fn new(keys: &[usize]) -> Self {
+        let mut elems: Vec<usize> = (0..keys.len()).collect();
+        elems.sort_by_key(|s| keys[*s]);
+        let mut part = Partition {
+            loc: vec![0; elems.len()],
+            block: vec![0; elems.len()],
+            first: Vec::new(), mid: Vec::new(), end: Vec::new(),
+            elems
+        };
+        for (i, s) in part.elems.iter().enumerate() {
+            if i == 0 || keys[*s] != keys[part.elems[i - 1]] {
+                part.first.push(i);
+                part.mid.push(i);
+                part.end.push(i);
             }
-            return self.accepts[state] != 0;
+            let b = part.first.len() - 1;
+            part.end[b] = i + 1;
+            part.loc[*s] = i;
+            part.block[*s] = b;
+        }
+        return part;
+    }
+
+    fn len(&self) -> usize {
+        return self.first.len();
+    }
+
+    fn size(&self, b: usize) -> usize {
+        return self.end[b] - self.first[b];
+    }
+
+    fn members(&self, b: usize) -> &[usize] {
+        return &self.elems[self.first[b]..self.end[b]];
+    }
+
+    // Marks s, true if it is the first mark in its block.
+    fn mark(&mut self, s: usize) -> bool {
+        let b = self.block[s];
+        let i = self.loc[s];
+        if i < self.mid[b] { return false; }
+        let m = self.mid[b];
+        self.elems.swap(i, m);
+        self.loc[self.elems[i]] = i;
+        self.loc[s] = m;
+        self.mid[b] += 1;
+        return m == self.first[b];
+    }
+
+    // Moves the marked states of b to a new block, unless all of b is
+    // marked, and clears the marks.
+    fn split(&mut self, b: usize) -> Option<usize> {
+        let (first, mid) = (self.first[b], self.mid[b]);
+        if mid == self.end[b] {
+            self.mid[b] = first;
+            return None;
+        }
+        let nb = self.first.len();
+        self.first.push(first);
+        self.mid.push(first);
+        self.end.push(mid);
+        self.first[b] = mid;
+        for s in &self.elems[first..mid] { self.block[*s] = nb; }
+        return Some(nb);
+    }
+}
+
+#[cfg(test)]
+impl DFA {
+    /// The rule accepting `word` read from the start state, or 0 if none does.
+    pub(crate) fn run(&self, word: impl AsRef<[u8]>) -> usize {
+        let mut state = 0;
+        for c in word.as_ref() {
+            let nxt = self.next(state, *c);
+            if nxt == NULL { return 0; }
+            state = nxt;
         }
+        return self.accepts[state];
     }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{path::Path, fs::File, io::{BufReader, BufRead}};
+    use std::collections::{BTreeSet, HashSet};
+    use crate::{lexer::Lexer, parser::{Parser, parse_spec}, nfa::{NFA, Encoding}, simplify};
+    use crate::{derivative, direct};
+    use super::*;
 
     #[test]
     fn test_matches_uncompressed() {
         let path = "tests/data/regex/input";
         let mut i = 0;
         while Path::new(&format!("{path}/match-{i}.txt")).exists() {
-            println!("{}", format!("{path}/match-{i}.txt"));
+            println!("{path}/match-{i}.txt");
             let lexer = Lexer::new(&format!("{path}/match-{i}.txt")).expect("Invalid Path");
             let mut parser = Parser::new(lexer).expect("File should be non-empty!");
-            let nfa = NFA::build_from_matches(&parser.parse().expect("Invalid parse"));
+            let nfa = NFA::build_from_matches(&parser.parse().expect("Invalid parse")).unwrap();
             let dfa = DFA::subset_construction(nfa);
 
             // dfa.print_dot();
             for id in ["right", "wrong"] {
-                let file = File::open(&format!("{path}/{id}-words-{i}.txt"))
+                let file = File::open(format!("{path}/{id}-words-{i}.txt"))
                     .expect("Should be valid...");
                 let reader = BufReader::new(file);
-                for line in reader.lines() {
-                    if let Ok(word) = line {
-                        assert!(dfa.accepts(&word) == (id == "right"));
-                    }
+                for word in reader.lines().map_while(Result::ok) {
+                    assert!((dfa.run(&word) != 0) == (id == "right"));
                 }
             }
             i += 1;
@@ -248,22 +552,285 @@ mod tests {
         let path = "tests/data/regex/input";
         let mut i = 0;
         while Path::new(&format!("{path}/match-{i}.txt")).exists() {
-            println!("{}", format!("{path}/match-{i}.txt"));
+            println!("{path}/match-{i}.txt");
+            let lexer = Lexer::new(&format!("{path}/match-{i}.txt")).expect("Invalid Path");
+            let mut parser = Parser::new(lexer).expect("File should be non-empty!");
+            let nfa = NFA::build_from_matches(&parser.parse().expect("Invalid parse")).unwrap();
+            let dfa = DFA::compress(DFA::subset_construction(nfa));
+            for id in ["right", "wrong"] {
+                let file = File::open(format!("{path}/{id}-words-{i}.txt"))
+                    .expect("Should be valid...");
+                let reader = BufReader::new(file);
+                for word in reader.lines().map_while(Result::ok) {
+                    assert!((dfa.run(&word) != 0) == (id == "right"));
+                }
+            }
+            i += 1;
+        }
+    }
+
+    #[test]
+    fn test_matches_simplified() {
+        let path = "tests/data/regex/input";
+        let mut i = 0;
+        while Path::new(&format!("{path}/match-{i}.txt")).exists() {
+            println!("{path}/match-{i}.txt");
             let lexer = Lexer::new(&format!("{path}/match-{i}.txt")).expect("Invalid Path");
             let mut parser = Parser::new(lexer).expect("File should be non-empty!");
-            let nfa = NFA::build_from_matches(&parser.parse().expect("Invalid parse"));
+            let mut matches = parser.parse().expect("Invalid parse");
+            simplify::simplify_matches(&mut matches);
+            let nfa = NFA::build_from_matches(&matches).unwrap();
             let dfa = DFA::compress(DFA::subset_construction(nfa));
             for id in ["right", "wrong"] {
-                let file = File::open(&format!("{path}/{id}-words-{i}.txt"))
+                let file = File::open(format!("{path}/{id}-words-{i}.txt"))
                     .expect("Should be valid...");
                 let reader = BufReader::new(file);
-                for line in reader.lines() {
-                    if let Ok(word) = line {
-                        assert!(dfa.accepts(&word) == (id == "right"));
+                for word in reader.lines().map_while(Result::ok) {
+                    assert!((dfa.run(&word) != 0) == (id == "right"));
+                }
+            }
+            i += 1;
+        }
+    }
+
+    #[test]
+    fn compress_merges() {
+        // Both states after the first byte merge, and so do both that accept.
+        let matches = crate::parser::parse_spec("\"ab|cb\" X;").expect("Valid spec");
+        let dfa = DFA::compress(DFA::subset_construction(NFA::build_from_matches(&matches).unwrap()));
+        assert!(dfa.ncount == 4);
+        assert!(dfa.jumps.iter().flatten().all(|s| *s == NULL || *s < dfa.ncount));
+        for (word, ok) in [("ab", true), ("cb", true), ("a", false), ("ac", false), ("abb", false)] {
+            assert!((dfa.run(word) != 0) == ok, "{word}");
+        }
+    }
+
+    #[test]
+    fn only_whitespace_restarts() {
+        // The generator skips all read so far on entering the start state.
+        let specs: [(&str, &[&str]); 3] = [
+            ("\"(ab)*c\" X; \"[ \\xa0]\" ;", &[" ", "\u{a0}"]),
+            ("\"(ab)*c\" X;", &[]),
+            ("\"a*\" A; \" \" ;", &[" "])
+        ];
+        for (spec, whitespace) in specs {
+            let matches = parse_spec(spec).expect("Valid spec");
+            let dfas = [
+                DFA::subset_construction(NFA::build_with_encoding(&matches, Encoding::Utf8).unwrap()),
+                direct::build_from_matches(&matches, Encoding::Utf8).unwrap(),
+                derivative::build_from_matches(&matches, Encoding::Utf8).unwrap()
+            ];
+            for (k, dfa) in dfas.map(DFA::compress).into_iter().enumerate() {
+                // The last byte of a whitespace character read from the start.
+                let allowed: Vec<(usize, usize)> = whitespace.iter().map(|ws| {
+                    let (last, init) = ws.as_bytes().split_last().unwrap();
+                    let state = init.iter().fold(0, |s, c| dfa.next(s, *c));
+                    (state, dfa.classes.get(*last))
+                }).collect();
+                for (s, row) in dfa.jumps.iter().enumerate() {
+                    for (c, nxt) in row.iter().enumerate() {
+                        assert!(*nxt != 0 || allowed.contains(&(s, c)), "{spec} {k}: {s} -{c}-> 0");
                     }
                 }
             }
+        }
+    }
+
+    // A bijection between the states reachable from each start that
+    // respects jumps and accepts.
+    fn isomorphic(a: &DFA, b: &DFA) -> bool {
+        let mut map = vec![NULL; a.ncount];
+        let mut stack = vec![(0, 0)];
+        map[0] = 0;
+        while let Some((s, t)) = stack.pop() {
+            if a.accepts[s] != b.accepts[t] { return false; }
+            for c in 0..=u8::MAX {
+                let (x, y) = (a.next(s, c), b.next(t, c));
+                if map[x] == NULL {
+                    map[x] = y;
+                    stack.push((x, y));
+                } else if map[x] != y {
+                    return false;
+                }
+            }
+        }
+        return a.ncount == b.ncount && map.iter().all(|m| *m != NULL);
+    }
+
+    #[test]
+    fn compress_empty() {
+        assert!(DFA::compress(DFA::new()).ncount == 0);
+        assert!(DFA::compress_table(DFA::new()).ncount == 0);
+    }
+
+    #[test]
+    fn hopcroft_matches_table() {
+        let mut paths: Vec<String> = Vec::new();
+        let mut i = 0;
+        while Path::new(&format!("tests/data/regex/input/match-{i}.txt")).exists() {
+            paths.push(format!("tests/data/regex/input/match-{i}.txt"));
             i += 1;
         }
+        // The table fill is too slow for the Unicode specs.
+        paths.extend((0..4).map(|i| format!("tests/tester/data/gen-{i}.tk")));
+        for path in paths {
+            let spec = std::fs::read_to_string(&path).expect("Invalid Path");
+            let matches = parse_spec(&spec).expect("Valid spec");
+            let nfa = || NFA::build_from_matches(&matches).unwrap();
+            let hopcroft = DFA::compress(DFA::subset_construction(nfa()));
+            let table = DFA::compress_table(DFA::subset_construction(nfa()));
+            assert!(isomorphic(&hopcroft, &table), "{path}");
+        }
+    }
+
+    #[test]
+    fn no_duplicate_states() {
+        let spec = std::fs::read_to_string("tests/tester/data/gen-5.tk").expect("Invalid Path");
+        let matches = parse_spec(&spec).expect("Valid spec");
+        let nfa = NFA::build_from_matches(&matches).unwrap().remove_eps();
+        let dfa = DFA::subset_construction(NFA::build_from_matches(&matches).unwrap());
+        // Every reachable set of NFA states, as order-free sets.
+        let mut seen: HashSet<BTreeSet<usize>> = HashSet::from([BTreeSet::from([0])]);
+        let mut stack = vec![BTreeSet::from([0])];
+        while let Some(set) = stack.pop() {
+            for c in 0..=u8::MAX {
+                let next: BTreeSet<usize> = set.iter()
+                    .flat_map(|s| nfa.next(*s, c))
+                    .collect();
+                if seen.insert(next.clone()) { stack.push(next); }
+            }
+        }
+        assert!(dfa.ncount == seen.len());
+    }
+
+    #[test]
+    fn keyword_ties() {
+        let label = |dfa: &DFA, word: &str| {
+            let mut state = 0;
+            for c in word.bytes() { state = dfa.next(state, c); }
+            dfa.labels[dfa.accepts[state] - 1].clone()
+        };
+        let specs = [
+            ("\"while\" WHILE; \"[a-z]+\" IDENT;", "WHILE"),
+            ("\"[a-z]+\" IDENT; \"while\" WHILE;", "IDENT"),
+            ("\"[a-z]+\" IDENT; \"while\" WHILE @1;", "WHILE"),
+            ("\"[a-z]+\" IDENT @-1; \"while\" WHILE;", "WHILE"),
+            ("\"[a-z]+\" IDENT @1; \"while\" WHILE @1;", "IDENT")
+        ];
+        for (spec, winner) in specs {
+            let matches = parse_spec(spec).expect("Valid spec");
+            let dfas = [
+                DFA::compress(DFA::subset_construction(NFA::build_from_matches(&matches).unwrap())),
+                direct::build_from_matches(&matches, Encoding::Utf8).unwrap(),
+                derivative::build_from_matches(&matches, Encoding::Utf8).unwrap()
+            ];
+            for dfa in &dfas {
+                assert!(label(dfa, "while") == winner, "{spec}");
+                assert!(label(dfa, "whilst") == "IDENT", "{spec}");
+            }
+        }
+    }
+
+    #[test]
+    fn byte_classes() {
+        let classes = ByteClasses::from_ranges([(b'a', b'z'), (b'i', b'i'), (0xFF, 0xFF)]);
+        assert!(classes.len() == 6);
+        assert!(classes.get(0) == 0 && classes.get(b'a' - 1) == 0);
+        assert!(classes.get(b'a') == classes.get(b'h') && classes.get(b'h') != classes.get(b'i'));
+        assert!(classes.get(b'j') == classes.get(b'z') && classes.get(b'z') != classes.get(b'{'));
+        assert!(classes.get(0xFF) == 5 && ByteClasses::identity().get(0xFF) == 0xFF);
+
+        let matches = parse_spec("\"if\" IF; \"[a-z]+\" ID; \" \" ;").expect("Valid spec");
+        let dfa = DFA::compress(DFA::subset_construction(NFA::build_from_matches(&matches).unwrap()));
+        assert!(dfa.classes.len() < 10 && dfa.jumps.iter().all(|row| row.len() == dfa.classes.len()));
+        assert!(dfa.run("  if") != 0 && dfa.run("ifz") != 0 && dfa.run("i f") == 0);
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn limits() {
+        let blowup = format!("\"(a|b)*a{}\" X; \"[a-z]\" ID; \"b+\" B;", "(a|b)".repeat(14));
+        let matches = parse_spec(&blowup).expect("Valid spec");
+        let nfa = || NFA::build_from_matches(&matches).unwrap();
+        let limits = Limits { dfa_states: 1000, ..Limits::default() };
+        let err = DFA::subset_construction_with(nfa(), &limits).err().expect("Too many states");
+        assert!(err.limit == Limit::DfaStates(1000) && err.rules[0].0 == "X");
+        assert!(err.to_string().starts_with("DFA exceeded 1000 states; most states come from X ("));
+
+        // The NFA stops growing after the first rule over the limit.
+        let limits = Limits { nfa_states: 10, ..Limits::default() };
+        let err = NFA::build_with_limits(&matches, Encoding::Utf8, &limits).err().expect("Too many states");
+        let err = err.downcast_ref::<LimitError>().expect("A limit error");
+        assert!(err.limit == Limit::NfaStates(10) && err.rules == [("X".to_string(), 94)]);
+        let limits = Limits { time: Duration::ZERO, ..Limits::default() };
+        assert!(DFA::subset_construction_with(nfa(), &limits).is_err());
+
+        // The direct and derivative constructions stop on the DFA limits.
+        let limits = Limits { dfa_states: 1000, ..Limits::default() };
+        for dfa in [
+            direct::build_from_matches_with(&matches, Encoding::Utf8, &limits),
+            derivative::build_from_matches_with(&matches, Encoding::Utf8, &limits)
+        ] {
+            let err = dfa.err().expect("Too many states");
+            assert!(err.downcast_ref::<LimitError>().map(|e| e.limit) == Some(Limit::DfaStates(1000)));
+        }
+        let limits = Limits { time: Duration::ZERO, ..Limits::default() };
+        assert!(direct::build_from_matches_with(&matches, Encoding::Utf8, &limits).is_err());
+        assert!(derivative::build_from_matches_with(&matches, Encoding::Utf8, &limits).is_err());
+
+        let matches = parse_spec("\"[a-z]+\" ID; \"b+\" B;").expect("Valid spec");
+        let limits = Limits { dfa_states: 10, ..Limits::default() };
+        assert!(DFA::subset_construction_with(NFA::build_from_matches(&matches).unwrap(), &limits).is_ok());
+    }
+
+    #[test]
+    fn dead_state() {
+        let sink = |dfa: &DFA| dfa.accepts[dfa.dead] == 0 &&
+            dfa.jumps[dfa.dead].iter().all(|t| *t == dfa.dead);
+        // No input leads to the dead state, but it is still there, and
+        // the start state is not merged into the state every byte leads to.
+        let matches = parse_spec("\"(\\p{L}|\\P{L})*\" ALL;").expect("Valid spec");
+        let nfa = NFA::build_with_encoding(&matches, Encoding::Latin1).unwrap();
+        let dfa = DFA::subset_construction(nfa);
+        assert!(dfa.dead == 1 && sink(&dfa));
+        let dfa = DFA::compress(dfa);
+        assert!(dfa.ncount == 3 && dfa.dead == 1 && sink(&dfa));
+        assert!(dfa.next(0, b'x') == 2 && dfa.next(2, b'x') == 2);
+
+        // The start state of a whitespace-only spec behaves like the dead
+        // state, but is kept apart from it.
+        let matches = parse_spec("\" \" ;").expect("Valid spec");
+        let dfa = DFA::compress(DFA::subset_construction(NFA::build_from_matches(&matches).unwrap()));
+        assert!(dfa.ncount == 2 && dfa.dead != 0 && sink(&dfa));
+        assert!(dfa.next(0, b' ') == 0 && dfa.next(0, b'x') == dfa.dead);
+        let table = DFA::compress_table(DFA::subset_construction(NFA::build_from_matches(&matches).unwrap()));
+        assert!(isomorphic(&dfa, &table) && table.dead == dfa.dead);
+
+        // The other constructions keep them apart too.
+        for spec in ["\" \" ;", "\"[a-z]+\" ID; \" \" ;"] {
+            let matches = parse_spec(spec).expect("Valid spec");
+            for dfa in [
+                direct::build_from_matches(&matches, Encoding::Utf8).unwrap(),
+                derivative::build_from_matches(&matches, Encoding::Utf8).unwrap()
+            ] {
+                assert!(dfa.dead != 0 && sink(&dfa), "{spec}");
+                assert!(dfa.next(0, b' ') == 0 && dfa.next(0, b'_') == dfa.dead, "{spec}");
+                let dfa = DFA::compress(dfa);
+                assert!(dfa.dead != 0 && sink(&dfa), "{spec}");
+                assert!(dfa.next(0, b' ') == 0 && dfa.next(0, b'_') == dfa.dead, "{spec}");
+            }
+        }
+
+        // No nonspacing mark is a Latin-1 byte, so the rule matches
+        // nothing, yet the start state is still not the dead state.
+        let matches = parse_spec("\"\\p{Mn}\" X;").expect("Valid spec");
+        for dfa in [
+            DFA::subset_construction(NFA::build_with_encoding(&matches, Encoding::Latin1).unwrap()),
+            direct::build_from_matches(&matches, Encoding::Latin1).unwrap(),
+            derivative::build_from_matches(&matches, Encoding::Latin1).unwrap()
+        ] {
+            assert!(dfa.dead != 0 && sink(&dfa));
+            let dfa = DFA::compress(dfa);
+            assert!(dfa.dead != 0 && sink(&dfa) && dfa.next(0, b'x') == dfa.dead);
+        }
+    }
+}
@@ -0,0 +1,326 @@
+use std::{collections::HashMap, error::Error, time::Instant};
+use crate::ast::{self, Match};
+use crate::dfa::{DFA, ALPHABET, NULL, Limit, LimitError, Limits};
+use crate::lexer::Op;
+use crate::nfa::{self, Encoding, BuildError};
+use crate::utf8;
+
+/// Builds a DFA straight from the rules, without an NFA, by the
+/// followpos construction of the Dragon Book (3.9). Every byte range
+/// read by a rule is a position, a DFA state is a set of positions,
+/// and reading a byte moves to the followpos of the positions that
+/// accept it. The rules are augmented as `WS* (r1 #1 | r2 #2 | ...)`,
+/// where WS alternates the whitespace rules and `#i` marks the end of
+/// rule i. The start state also holds a position of its own that
+/// follows each whole whitespace character, so only whitespace leads
+/// back to it, as in the other constructions.
+pub fn build_from_matches(matches: &[Match], encoding: Encoding) -> Result<DFA, BuildError> {
+    let (tree, start, labels) = augment(matches, encoding)?;
+    return Ok(tree.subset_construction(start, labels, &Limits::none())
+        .expect("Unlimited construction never fails"));
+}
+
+/// `build_from_matches`, giving up once it goes over the DFA state or
+/// time limit.
+pub fn build_from_matches_with(matches: &[Match], encoding: Encoding, limits: &Limits)
+    -> Result<DFA, Box<dyn Error>> {
+    let (tree, start, labels) = augment(matches, encoding)?;
+    return Ok(tree.subset_construction(start, labels, limits)?);
+}
+
+// The positions of the augmented tree, the positions of the start
+// state, and the rule labels in priority order.
+fn augment(matches: &[Match], encoding: Encoding)
+    -> Result<(Positions, Vec<usize>, Vec<String>), BuildError> {
+    let mut tree = Positions { pos: Vec::new(), encoding, rule: 0 };
+    let mut labels: Vec<String> = Vec::new();
+    let mut ws: Option<Info> = None;
+    let mut rules: Option<Info> = None;
+    for rule in ast::priority_order(matches) {
+        let m = &matches[rule];
+        tree.rule = rule;
+        let info = tree.visit(&m.root)?;
+        if m.name.is_empty() {
+            ws = Some(match ws {
+                Some(w) => alt(w, info),
+                None => info
+            });
+            continue;
+        }
+        labels.push(m.name.clone());
+        let end = tree.end(labels.len());
+        let rule = tree.cat(info, end);
+        rules = Some(match rules {
+            Some(r) => alt(r, rule),
+            None => rule
+        });
+    }
+    let mut root = rules.unwrap_or(Info::empty());
+    let restart = tree.make(None, 0);
+    if let Some(ws) = ws {
+        let ws = tree.star(ws);
+        for p in &ws.last { tree.pos[*p].follow.push(restart); }
+        root = tree.cat(ws, root);
+    }
+    let mut start = root.first;
+    start.push(restart);
+    return Ok((tree, start, labels));
+}
+
+// A leaf of the augmented tree: a byte range, or the end of a rule.
+struct Pos {
+    range:  Option<(u8, u8)>,
+    accept: usize,
+    follow: Vec<usize>
+}
+
+// nullable, firstpos and lastpos of a subtree.
+struct Info {
+    nullable: bool,
+    first: Vec<usize>,
+    last: Vec<usize>
+}
+
+impl Info {
+    fn empty() -> Self {
+        return Info { nullable: false, first: Vec::new(), last: Vec::new() };
+    }
+}
+
+fn alt(mut a: Info, b: Info) -> Info {
+    a.nullable |= b.nullable;
+    a.first.extend(b.first);
+    a.last.extend(b.last);
+    return a;
+}
+
+struct Positions {
+    pos: Vec<Pos>,
+    encoding: Encoding,
+    // The rule being visited, for errors.
+    rule: usize
+}
+
+impl Positions {
+    fn visit(&mut self, node: &ast::Node) -> Result<Info, BuildError> {
+        return match node {
+            ast::Node::Char(c) => self.class(&[(*c, *c)]),
+            ast::Node::Class(ranges) => self.class(ranges),
+            ast::Node::Property(p) => self.class(&nfa::property_ranges(*p, self.encoding)),
+            ast::Node::Capture(n) => self.visit(&n.child),
+            ast::Node::BinaryExpr(n) => match (n.op, &*n.left, &*n.right) {
+                (Op::DASH, ast::Node::Char(lo), ast::Node::Char(hi)) => self.class(&[(*lo, *hi)]),
+                (Op::DASH, _, _) => Err(BuildError::InvalidRange { rule: self.rule }),
+                (Op::BAR, _, _) => {
+                    let left = self.visit(&n.left)?;
+                    let right = self.visit(&n.right)?;
+                    Ok(alt(left, right))
+                },
+                (Op::AND, _, _) => {
+                    let left = self.visit(&n.left)?;
+                    let right = self.visit(&n.right)?;
+                    Ok(self.cat(left, right))
+                },
+                (op, _, _) => Err(BuildError::UnexpectedOp { rule: self.rule, op })
+            },
+            ast::Node::UnaryExpr(n) => {
+                let child = self.visit(&n.child)?;
+                match n.op {
+                    Op::STAR => Ok(self.star(child)),
+                    Op::PLUS => Ok(self.plus(child)),
+                    Op::QUESTION => Ok(Info { nullable: true, ..child }),
+                    op => Err(BuildError::UnexpectedOp { rule: self.rule, op })
+                }
+            }
+        };
+    }
+
+    fn cat(&mut self, a: Info, b: Info) -> Info {
+        for p in &a.last {
+            self.pos[*p].follow.extend_from_slice(&b.first);
+        }
+        let mut first = a.first;
+        if a.nullable { first.extend_from_slice(&b.first); }
+        let mut last = b.last;
+        if b.nullable { last.extend(a.last); }
+        return Info { nullable: a.nullable && b.nullable, first, last };
+    }
+
+    fn star(&mut self, child: Info) -> Info {
+        let child = self.plus(child);
+        return Info { nullable: true, ..child };
+    }
+
+    fn plus(&mut self, child: Info) -> Info {
+        for p in &child.last {
+            self.pos[*p].follow.extend_from_slice(&child.first);
+        }
+        return child;
+    }
+
+    fn end(&mut self, accept: usize) -> Info {
+        let p = self.make(None, accept);
+        return Info { nullable: false, first: vec![p], last: vec![p] };
+    }
+
+    // Each byte sequence of the class is a chain of positions. Chains
+    // with the same leading ranges share them, like a trie.
+    fn class(&mut self, ranges: &[(char, char)]) -> Result<Info, BuildError> {
+        let mut info = Info::empty();
+        let rule = self.rule;
+        let byte = |c: char| u8::try_from(c).map_err(|_| BuildError::OutOfRange { rule, c });
+        let seqs: Vec<Vec<(u8, u8)>> = match self.encoding {
+            Encoding::Utf8 => ranges.iter()
+                .flat_map(|(lo, hi)| utf8::sequences(*lo, *hi))
+                .collect(),
+            Encoding::Latin1 => ranges.iter()
+                .filter(|(lo, hi)| lo <= hi)
+                .map(|(lo, hi)| Ok(vec![(byte(*lo)?, byte(*hi)?)]))
+                .collect::<Result<_, BuildError>>()?
+        };
+        let mut prev: Vec<(u8, u8)> = Vec::new();
+        let mut path: Vec<usize> = Vec::new();
+        for seq in seqs {
+            let mut k = 0;
+            while prev.len() == seq.len() && k + 1 < seq.len() && prev[k] == seq[k] {
+                k += 1;
+            }
+            path.truncate(k);
+            for (i, range) in seq.iter().enumerate().skip(k) {
+                let p = self.make(Some(*range), 0);
+                match i {
+                    0 => info.first.push(p),
+                    _ => self.pos[path[i - 1]].follow.push(p)
+                }
+                path.push(p);
+            }
+            info.last.push(path[seq.len() - 1]);
+            prev = seq;
+        }
+        return Ok(info);
+    }
+
+    fn make(&mut self, range: Option<(u8, u8)>, accept: usize) -> usize {
+        self.pos.push(Pos { range, accept, follow: Vec::new() });
+        return self.pos.len() - 1;
+    }
+
+    fn subset_construction(&self, start: Vec<usize>, labels: Vec<String>, limits: &Limits)
+        -> Result<DFA, LimitError> {
+        let begin = Instant::now();
+        let mut dfa = DFA::new();
+        dfa.labels = labels;
+        let mut d_states: Vec<Vec<usize>> = Vec::new();
+        let mut seen: HashMap<Vec<usize>, usize> = HashMap::new();
+        self.state(start, &mut dfa, &mut d_states, &mut seen);
+        // The dead state for the empty set, which is there even if no
+        // input reaches it. The start set is never empty, as it holds the
+        // restart position.
+        dfa.dead = self.state(Vec::new(), &mut dfa, &mut d_states, &mut seen);
+        let mut index = 0;
+        while index < d_states.len() {
+            if dfa.ncount > limits.dfa_states {
+                return Err(LimitError { limit: Limit::DfaStates(limits.dfa_states), rules: Vec::new() });
+            }
+            if begin.elapsed() > limits.time {
+                return Err(LimitError { limit: Limit::Time(limits.time), rules: Vec::new() });
+            }
+            let mut moves: Vec<Vec<usize>> = vec![Vec::new(); ALPHABET];
+            for p in &d_states[index] {
+                if let Some((lo, hi)) = self.pos[*p].range {
+                    for c in lo..=hi {
+                        moves[c as usize].extend_from_slice(&self.pos[*p].follow);
+                    }
+                }
+            }
+            for (c, mv) in moves.into_iter().enumerate() {
+                dfa.jumps[index][c] = self.state(mv, &mut dfa, &mut d_states, &mut seen);
+            }
+            index += 1;
+        }
+        return Ok(dfa);
+    }
+
+    // The DFA state for a set of positions, made on first sight. A state
+    // ending several rules accepts the one that wins ties.
+    fn state(&self, set: Vec<usize>, dfa: &mut DFA, d_states: &mut Vec<Vec<usize>>,
+        seen: &mut HashMap<Vec<usize>, usize>) -> usize {
+        let set = canonical(set);
+        if let Some(u) = seen.get(&set) { return *u; }
+        let accept = set.iter()
+            .map(|p| self.pos[*p].accept)
+            .filter(|a| *a != 0)
+            .min()
+            .unwrap_or(0);
+        seen.insert(set.clone(), dfa.ncount);
+        d_states.push(set);
+        dfa.jumps.push(vec![NULL; ALPHABET]);
+        dfa.accepts.push(accept);
+        dfa.ncount += 1;
+        return dfa.ncount - 1;
+    }
+}
+
+fn canonical(mut set: Vec<usize>) -> Vec<usize> {
+    set.sort_unstable();
+    set.dedup();
+    return set;
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{path::Path, fs::File, io::{BufReader, BufRead}};
+    use crate::{lexer::Lexer, parser::{Parser, parse_spec}, nfa::NFA};
+    use super::*;
+
+    #[test]
+    fn test_matches() {
+        let path = "tests/data/regex/input";
+        let mut i = 0;
+        while Path::new(&format!("{path}/match-{i}.txt")).exists() {
+            let lexer = Lexer::new(&format!("{path}/match-{i}.txt")).expect("Invalid Path");
+            let mut parser = Parser::new(lexer).expect("File should be non-empty!");
+            let matches = parser.parse().expect("Invalid parse");
+            let dfa = build_from_matches(&matches, Encoding::Utf8).unwrap();
+            let compressed = DFA::compress(build_from_matches(&matches, Encoding::Utf8).unwrap());
+            for id in ["right", "wrong"] {
+                let file = File::open(format!("{path}/{id}-words-{i}.txt"))
+                    .expect("Should be valid...");
+                let reader = BufReader::new(file);
+                for word in reader.lines().map_while(Result::ok) {
+                    assert!((dfa.run(&word) != 0) == (id == "right"), "{word}");
+                    assert!((compressed.run(&word) != 0) == (id == "right"), "{word}");
+                }
+            }
+            i += 1;
+        }
+    }
+
+    #[test]
+    fn same_as_thompson() {
+        let spec = "\"if\" IF; \"[a-z]+\" ID; \"[0-9]+(.[0-9]*)?\" NUM; \"λ|\\p{Lu}\" X;";
+        let matches = parse_spec(spec).unwrap();
+        let direct = DFA::compress(build_from_matches(&matches, Encoding::Utf8).unwrap());
+        let nfa = NFA::build_from_matches(&matches).unwrap();
+        let thompson = DFA::compress(DFA::subset_construction(nfa));
+        assert!(direct.ncount == thompson.ncount);
+        for word in ["if", "iff", "x", "12.", "1.5", "λ", "ω", "", ".", "1a", "Λ"] {
+            assert!(direct.run(word) == thompson.run(word), "{word}");
+        }
+    }
+
+    #[test]
+    fn whitespace() {
+        let matches = parse_spec("\"ab\" AB; \"[ \\t]\" ;").unwrap();
+        let dfa = build_from_matches(&matches, Encoding::Utf8).unwrap();
+        assert!(dfa.next(0, b' ') == 0 && dfa.next(0, b'\t') == 0);
+        assert!(dfa.run("  \tab") != 0 && dfa.run("a b") == 0);
+        assert!(dfa.jumps[dfa.dead].iter().all(|s| *s == dfa.dead));
+
+        // The same positions as at the start, but without whitespace read.
+        let matches = parse_spec("\"(ab)*c\" X;").unwrap();
+        let dfa = build_from_matches(&matches, Encoding::Utf8).unwrap();
+        let after = |word: &str| word.bytes().fold(0, |s, c| dfa.next(s, c));
+        assert!(after("ab") != 0 && after("abab") == after("ab") && dfa.run("ababc") != 0);
+    }
+}
@@ -0,0 +1,211 @@
+use crate::ast::{Node, Match};
+use crate::printer;
+
+/// Serialises `matches` as a JSON array of rules. Each rule carries its
+/// name, priority, source span, printed regex and AST.
+pub fn to_json(matches: &[Match]) -> String {
+    let mut out = String::from("[");
+    for (i, m) in matches.iter().enumerate() {
+        if i > 0 { out.push(','); }
+        out.push_str(&format!(
+            "\n  {{\n    \"name\": {},\n    \"priority\": {},\n    \"span\": {{\"start\": {}, \"end\": {}}},\n    \"regex\": {},\n    \"root\": ",
+            json_str(&m.name), m.priority, m.span.start, m.span.end,
+            json_str(&printer::regex(&m.root))
+        ));
+        json_node(&m.root, 2, &mut out);
+        out.push_str("\n  }");
+    }
+    if !matches.is_empty() { out.push('\n'); }
+    out.push_str("]\n");
+    return out;
+}
+
+fn json_node(node: &Node, depth: usize, out: &mut String) {
+    let tabs = "  ".repeat(depth + 1);
+    match node {
+        Node::Char(c) => {
+            out.push_str(&format!("{{\"type\": \"char\", \"value\": {}}}",
+                json_str(&c.to_string())
+            ));
+        },
+        Node::Class(ranges) => {
+            let ranges: Vec<String> = ranges.iter()
+                .map(|(lo, hi)| format!("[{}, {}]",
+                    json_str(&lo.to_string()), json_str(&hi.to_string())
+                ))
+                .collect();
+            out.push_str(&format!("{{\"type\": \"class\", \"ranges\": [{}]}}",
+                ranges.join(", ")
+            ));
+        },
+        Node::Property(p) => {
+            out.push_str(&format!("{{\"type\": \"property\", \"name\": {}, \"negated\": {}}}",
+                json_str(p.name()), p.negated
+            ));
+        },
+        Node::BinaryExpr(n) => {
+            out.push_str(&format!("{{\n{tabs}\"type\": \"binary\",\n{tabs}\"op\": \"{:?}\",\n{tabs}\"left\": ", n.op));
+            json_node(&n.left, depth + 1, out);
+            out.push_str(&format!(",\n{tabs}\"right\": "));
+            json_node(&n.right, depth + 1, out);
+            out.push_str(&format!("\n{}}}", "  ".repeat(depth)));
+        },
+        Node::UnaryExpr(n) => {
+            out.push_str(&format!("{{\n{tabs}\"type\": \"unary\",\n{tabs}\"op\": \"{:?}\",\n{tabs}\"child\": ", n.op));
+            json_node(&n.child, depth + 1, out);
+            out.push_str(&format!("\n{}}}", "  ".repeat(depth)));
+        },
+        Node::Capture(n) => {
+            out.push_str(&format!("{{\n{tabs}\"type\": \"capture\",\n{tabs}\"name\": {},\n{tabs}\"child\": ", json_str(&n.name)));
+            json_node(&n.child, depth + 1, out);
+            out.push_str(&format!("\n{}}}", "  ".repeat(depth)));
+        }
+    }
+}
+
+fn json_str(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"'  => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\t' => out.push_str("\\t"),
+            '\r' => out.push_str("\\r"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c)
+        }
+    }
+    out.push('"');
+    return out;
+}
+
+/// Serialises `matches` as an XML document with one <rule> per match.
+/// Characters are written as code points, since XML 1.0 cannot carry
+/// most control characters even when escaped.
+pub fn to_xml(matches: &[Match]) -> String {
+    let mut out = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n<spec>\n");
+    for m in matches {
+        out.push_str(&format!("  <rule name=\"{}\" priority=\"{}\" start=\"{}\" end=\"{}\" regex=\"{}\">\n",
+            xml_str(&m.name), m.priority, m.span.start, m.span.end,
+            xml_str(&printer::regex(&m.root))
+        ));
+        xml_node(&m.root, 2, &mut out);
+        out.push_str("  </rule>\n");
+    }
+    out.push_str("</spec>\n");
+    return out;
+}
+
+fn xml_node(node: &Node, depth: usize, out: &mut String) {
+    let tabs = "  ".repeat(depth);
+    match node {
+        Node::Char(c) => {
+            out.push_str(&format!("{tabs}<char code=\"{}\"/>\n", *c as u32));
+        },
+        Node::Class(ranges) => {
+            out.push_str(&format!("{tabs}<class>\n"));
+            for (lo, hi) in ranges {
+                out.push_str(&format!("{tabs}  <range lo=\"{}\" hi=\"{}\"/>\n",
+                    *lo as u32, *hi as u32
+                ));
+            }
+            out.push_str(&format!("{tabs}</class>\n"));
+        },
+        Node::Property(p) => {
+            out.push_str(&format!("{tabs}<property name=\"{}\" negated=\"{}\"/>\n",
+                p.name(), p.negated
+            ));
+        },
+        Node::BinaryExpr(n) => {
+            out.push_str(&format!("{tabs}<binary op=\"{:?}\">\n", n.op));
+            xml_node(&n.left, depth + 1, out);
+            xml_node(&n.right, depth + 1, out);
+            out.push_str(&format!("{tabs}</binary>\n"));
+        },
+        Node::UnaryExpr(n) => {
+            out.push_str(&format!("{tabs}<unary op=\"{:?}\">\n", n.op));
+            xml_node(&n.child, depth + 1, out);
+            out.push_str(&format!("{tabs}</unary>\n"));
+        },
+        Node::Capture(n) => {
+            out.push_str(&format!("{tabs}<capture name=\"{}\">\n", xml_str(&n.name)));
+            xml_node(&n.child, depth + 1, out);
+            out.push_str(&format!("{tabs}</capture>\n"));
+        }
+    }
+}
+
+fn xml_str(s: &str) -> String {
+    let mut out = String::new();
+    for c in s.chars() {
+        match c {
+            '&'  => out.push_str("&amp;"),
+            '<'  => out.push_str("&lt;"),
+            '>'  => out.push_str("&gt;"),
+            '"'  => out.push_str("&quot;"),
+            '\'' => out.push_str("&apos;"),
+            '\t' | '\n' | '\r' => out.push_str(&format!("&#{};", c as u32)),
+            c if (c as u32) < 0x20 => out.push('\u{FFFD}'),
+            c => out.push(c)
+        }
+    }
+    return out;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::parse_spec;
+
+    #[test]
+    fn json() {
+        let matches = parse_spec("\"a*[0-9]\" NUM;\n\"\\\"\" ;").unwrap();
+        let ans = concat!(
+            "[\n",
+            "  {\n",
+            "    \"name\": \"NUM\",\n",
+            "    \"priority\": 0,\n",
+            "    \"span\": {\"start\": 0, \"end\": 14},\n",
+            "    \"regex\": \"a*[0-9]\",\n",
+            "    \"root\": {\n",
+            "      \"type\": \"binary\",\n",
+            "      \"op\": \"AND\",\n",
+            "      \"left\": {\n",
+            "        \"type\": \"unary\",\n",
+            "        \"op\": \"STAR\",\n",
+            "        \"child\": {\"type\": \"char\", \"value\": \"a\"}\n",
+            "      },\n",
+            "      \"right\": {\"type\": \"class\", \"ranges\": [[\"0\", \"9\"]]}\n",
+            "    }\n",
+            "  },\n",
+            "  {\n",
+            "    \"name\": \"\",\n",
+            "    \"priority\": 0,\n",
+            "    \"span\": {\"start\": 15, \"end\": 21},\n",
+            "    \"regex\": \"\\\\\\\"\",\n",
+            "    \"root\": {\"type\": \"char\", \"value\": \"\\\"\"}\n",
+            "  }\n",
+            "]\n"
+        );
+        assert!(to_json(&matches) == ans, "{}", to_json(&matches));
+        assert!(to_json(&[]) == "[]\n");
+    }
+
+    #[test]
+    fn xml() {
+        let matches = parse_spec("\"<|&\" OP;").unwrap();
+        let ans = concat!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+            "<spec>\n",
+            "  <rule name=\"OP\" priority=\"0\" start=\"0\" end=\"9\" regex=\"&lt;|&amp;\">\n",
+            "    <binary op=\"BAR\">\n",
+            "      <char code=\"60\"/>\n",
+            "      <char code=\"38\"/>\n",
+            "    </binary>\n",
+            "  </rule>\n",
+            "</spec>\n"
+        );
+        assert!(to_xml(&matches) == ans, "{}", to_xml(&matches));
+    }
+}
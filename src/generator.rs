@@ -1,11 +1,14 @@
 use std::{fs::File, error::Error};
 use std::io::Write;
-use crate::dfa::{DFA, self};
+use crate::dfa::{DFA, ALPHABET};
+use crate::nfa::NFA;
 
 pub struct Generator<'a> { 
     dfa: &'a DFA,
     file: File,
     tabs: usize,
+    binary: bool,
+    captures: &'a [Option<NFA>],
 }
 
 #[allow(dead_code)]
@@ -15,8 +18,27 @@ impl<'a> Generator<'a> {
             dfa,
             file: File::create(outpath)?,
             tabs: 0,
+            binary: false,
+            captures: &[],
         });
     }
+
+    /// In binary mode the generated lexer scans raw bytes (`&[u8]`),
+    /// and tokens carry `Vec<u8>` instead of `String`.
+    pub fn set_binary(&mut self, binary: bool) {
+        self.binary = binary;
+    }
+
+    /// The tagged NFA of each named rule with capture groups, see
+    /// `NFA::build_rule`. Their tokens also carry the span of each group,
+    /// found by replaying the NFA over the token's text.
+    pub fn set_captures(&mut self, captures: &'a [Option<NFA>]) {
+        self.captures = captures;
+    }
+
+    fn tagged(&self, idx: usize) -> Option<&'a NFA> {
+        return self.captures.get(idx).and_then(|nfa| nfa.as_ref());
+    }
     fn write_inline(&mut self, s: &str) -> Result<(), Box<dyn Error>> {
         write!(self.file, "{}",s)?;
         return Ok(());
@@ -46,13 +68,21 @@ impl<'a> Generator<'a> {
         self.writeln("#[derive(Debug, PartialEq, Eq)]")?;
         self.writeln("pub enum Token {")?;
         self.indent();
-        for label in &self.dfa.labels {
-            if label.len() == 0 { continue; }
-            self.writeln(&format!("{label}(String),"))?;
+        let word = if self.binary { "Vec<u8>" } else { "String" };
+        for (idx, label) in self.dfa.labels.iter().enumerate() {
+            if label.is_empty() { continue; }
+            match self.tagged(idx) {
+                Some(_) => self.writeln(&format!("{label}({word}, Vec<Group>),"))?,
+                None => self.writeln(&format!("{label}({word}),"))?
+            }
         }
         self.writeln("EOF")?;
         self.unindent();
         self.writeln("}")?;
+        if self.captures.iter().any(|nfa| nfa.is_some()) {
+            self.write_captures()?;
+        }
+        self.write_tables()?;
         self.writeln("#[derive(Debug, PartialEq, Eq)]")?;
         self.write_vec(&[
             "pub struct TokenErr {",
@@ -61,7 +91,7 @@ impl<'a> Generator<'a> {
         ])?;
         self.write_vec(&[
             "pub struct Lexer {",
-            "  chars:   Vec<char>,",
+            "  chars:   Vec<u8>,",
             "  pos:     usize,",
             "  begins:  Vec<usize>,",
             "  tabs:    Vec<usize>,",
@@ -69,33 +99,55 @@ impl<'a> Generator<'a> {
             &format!("  accepts: [usize; {}]", self.dfa.ncount),
             "}",
             "impl Lexer {",
-            "    pub fn new(fname: &str) -> Result<Self, Box<dyn std::error::Error>> {",
-            "        let chars = fs::read_to_string(fname)?",
-            "            .chars()",
-            "            .collect();",
+        ])?;
+        if self.binary {
+            self.write_vec(&[
+                "    pub fn new(fname: &str) -> Result<Self, Box<dyn std::error::Error>> {",
+                "        return Ok(Lexer::from_bytes(&fs::read(fname)?));",
+                "    }",
+                "",
+                "    pub fn from_bytes(bytes: &[u8]) -> Self {",
+                "        let chars = bytes.to_vec();",
+            ])?;
+        } else {
+            self.write_vec(&[
+                "    pub fn new(fname: &str) -> Result<Self, Box<dyn std::error::Error>> {",
+                "        let chars = fs::read_to_string(fname)?",
+                "            .into_bytes();",
+            ])?;
+        }
+        // Text is scanned as UTF-8, so columns skip continuation bytes.
+        let (inc, dec) = if self.binary {
+            ("self.column += 1", "self.column -= 1")
+        } else {
+            ("if c & 0xC0 != 0x80 { self.column += 1 }",
+             "if c & 0xC0 != 0x80 { self.column -= 1 }")
+        };
+        let (ret, end) = if self.binary { ("Lexer {", "};") } else { ("Ok(Lexer {", "});") };
+        self.write_vec(&[
             &self.gen_accepts(),
-            "        return Ok(Lexer { ",
+            &format!("        return {ret} "),
             "           chars,",
             "           pos: 0,",
             "           begins: vec![0; 1],",
             "           tabs:   Vec::new(),",
             "           column: 0,",
             "           accepts",
-            "        });",
+            &format!("        {end}"),
             "    }",
             "",
-            "   fn advance(&mut self) -> char {",
+            "   fn advance(&mut self) -> u8 {",
             "       let c = self.chars[self.pos];",
             "        match c {",
-            "           '\\n' => {",
+            &format!("           {} => {{", self.lit(b'\n')),
             "               self.column = 0;",
             "               self.begins.push(self.pos + 1);",
             "           },",
-            "           '\\t' => {",
+            &format!("           {} => {{", self.lit(b'\t')),
             "               self.tabs.push(self.column);",
             "               self.column += 4 - (self.column % 4);",
             "           }",
-            "           _ => self.column += 1",
+            &format!("           _ => {inc}"),
             "       }",
             "       self.pos += 1;",
             "       return c;",
@@ -104,14 +156,14 @@ impl<'a> Generator<'a> {
             "       self.pos -= 1;",
             "       let c = self.chars[self.pos];",
             "       match c {",
-            "           '\\n' => {",
+            &format!("           {} => {{", self.lit(b'\n')),
             "               self.begins.pop();",
             "               self.column = self.pos - self.begins[self.begins.len() - 1];",
             "           }",
-            "           '\\t' => {",
+            &format!("           {} => {{", self.lit(b'\t')),
             "               self.column = self.tabs.pop().unwrap();",
             "           }",
-            "           _ => self.column -= 1",
+            &format!("           _ => {dec}"),
             "       }",
             "   }",
         ])?;
@@ -130,36 +182,32 @@ impl<'a> Generator<'a> {
         self.write_vec(&[
             "if self.pos == self.chars.len() { return Ok(EOF); }",
             "let mut stk: Vec<usize> = Vec::new();",
-            "let mut chars: Vec<char> = Vec::new();",
+            "let mut chars: Vec<u8> = Vec::new();",
             "let mut state: usize = 0;",
             "loop {",
+            "    if self.pos == self.chars.len() { break; }",
+            "    let c = self.advance();",
+            "    let next = JUMPS[state][CLASSES[c as usize] as usize] as usize;",
+            "    // Only whitespace leads back to the start, so all read so far is skipped.",
+            "    if next == 0 {",
+            "        state = 0;",
+            "        stk.clear();",
+            "        chars.clear();",
+            "        continue;",
+            "    }",
+            "    // No token goes on from here, so c is not part of one.",
+            "    if next == DEAD {",
+            "        self.retract();",
+            "        break;",
+            "    }",
+            "    state = next;",
+            "    stk.push(state);",
+            "    chars.push(c);",
+            "}",
         ])?;
-        self.indent();
-        self.writeln("if self.pos == self.chars.len() { break; }")?;
-        self.writeln("let c = self.advance();")?;
-        self.writeln("state = match state {")?;
-        self.indent();
-        for state in 0..self.dfa.ncount {
-            if state == self.dfa.dead {
-                self.write_vec(&[
-                    &format!("{} => {{", self.dfa.dead),
-                    "\tstk.push(state);",
-			        "\tchars.push(c);",
-                    "\tbreak;",
-                    "}"
-                ])?;
-            } else {
-                self.write_transitions(state)?;
-            }
-        }
-        self.writeln("_ => panic!(\"Invalid State!\")")?;
-        self.unindent();
-        self.writeln("};")?;
-        self.writeln("stk.push(state);")?;
-        self.writeln("chars.push(c);")?;
-        self.unindent();
-        self.writeln("}")?;
         self.write_vec(&[
+            "// Only whitespace was left.",
+            "if stk.len() == 0 && self.pos == self.chars.len() { return Ok(EOF); }",
             "while stk.len() > 0 &&",
             "   self.accepts[stk[stk.len() - 1]] == 0 {",
             "   stk.pop().unwrap();",
@@ -168,22 +216,34 @@ impl<'a> Generator<'a> {
             "}",
             "if stk.len() == 0 {",
             "    let start = self.begins[self.begins.len() - 1];",
-            "    let error_line: String = self.chars[start..]",
+            "    let error_line: Vec<u8> = self.chars[start..]",
             "        .iter()",
-            "        .take_while(|&&c| c != '\\n')",
+            "        .take_while(|&&c| c != b'\\n')",
+            "        .cloned()",
             "        .collect();",
             "    return Err(TokenErr{error: format!(",
             "        \"Failed to lex from: \\n{}\\n{}^\",",
-            "        error_line,",
+            "        String::from_utf8_lossy(&error_line),",
             "        \" \".repeat(self.column)",
             "    )});",
             "}"
         ])?;
-        self.writeln("let word : String = chars.iter().collect();")?;
+        if self.binary {
+            self.writeln("let word : Vec<u8> = chars;")?;
+        } else {
+            self.writeln("let word : String = String::from_utf8_lossy(&chars).into_owned();")?;
+        }
         self.writeln("match self.accepts[stk[stk.len() - 1]] {")?;
         self.indent();
         for (idx, label) in self.dfa.labels.iter().enumerate() {
-            if label.len() == 0 { continue; }
+            if label.is_empty() { continue; }
+            if self.tagged(idx).is_some() {
+                self.writeln(&format!(
+                    "{:<4} => {{ let groups = groups(&TAGGED_{}, word.as_ref()); return Ok({}(word, groups)) }},",
+                    idx + 1, idx, label
+                ))?;
+                continue;
+            }
             self.writeln(&format!(
                 "{:<4} => return Ok({}(word)),",
                 idx + 1, self.dfa.labels[idx],
@@ -195,57 +255,128 @@ impl<'a> Generator<'a> {
         return Ok(());
     }
 
-    fn write_transitions(&mut self, state: usize) -> Result<(), Box<dyn Error>> {
-        self.writeln(&format!("{state} => match c {{"))?;
-        self.indent();
-        let mut j = 0;
-        while j < u8::MAX {
-            let nbr = self.dfa.jumps[state][j as usize];
-            // the only self-transitions are from whitespace.
-            if state == 0 && nbr == 0 {
-                self.writeln(&format!("\'{}\' => {},",
-                    escape(j as char),
-                    "continue"
-                ))?;
-                j += 1;
-                continue;
-            }
-            if nbr == dfa::NULL { j += 1; continue; };
-            if self.dfa.dead == nbr { j += 1; continue; }
-            //println!("{} - {}", self.dfa.dead, nbr);
-            let start =  j;
-            while j + 1 < u8::MAX &&
-                self.dfa.jumps[state][(j + 1) as usize] == nbr { 
-                j += 1 
-            }
-            //println!("{}", self.dfa.jumps[state][j as usize]);
-            if start == j {
-                self.writeln(&format!("\'{}\' => {},",
-                    escape(start as char),
-                    self.dfa.jumps[state][j as usize]
-                ))?;
-            } else if start + 1 == j {
-                self.writeln(&format!("\'{}\' | \'{}\' => {},",
-                    escape(start as char), 
-                    escape(j as char),
-                    self.dfa.jumps[state][j as usize]
-                ))?;
-            } else {
-                self.writeln(&format!(
-                    "\'{}\'..=\'{}\' => {},",
-                    escape(start as char),
-                    escape(j as char),
-                    self.dfa.jumps[state][j as usize]
-                ))?;
-            }
-            j += 1;
+    // The class of each byte, and the next state for each state and
+    // class, in the smallest integer type that fits every state.
+    fn write_tables(&mut self) -> Result<(), Box<dyn Error>> {
+        let classes = &self.dfa.classes;
+        self.writeln("// CLASSES[b] is the class of byte b; bytes in a class move every state alike.")?;
+        self.writeln(&format!("const CLASSES: [u8; {ALPHABET}] = ["))?;
+        for row in classes.map().chunks(16) {
+            let row: Vec<String> = row.iter().map(|k| format!("{k:>3}")).collect();
+            self.writeln(&format!("    {},", row.join(", ")))?;
+        }
+        self.writeln("];")?;
+        let ty = if self.dfa.ncount <= u16::MAX as usize + 1 { "u16" } else { "u32" };
+        self.writeln("// JUMPS[s][k] is the state s moves to on a byte of class k.")?;
+        self.writeln(&format!(
+            "const JUMPS: [[{ty}; {}]; {}] = [", classes.len(), self.dfa.ncount
+        ))?;
+        for jumps in &self.dfa.jumps {
+            let row: Vec<String> = jumps.iter().map(|t| t.to_string()).collect();
+            let lines: Vec<String> = row.chunks(16).map(|line| line.join(", ")).collect();
+            self.writeln(&format!("    [{}],", lines.join(",\n     ")))?;
+        }
+        self.writeln("];")?;
+        self.writeln(&format!("const DEAD: usize = {};", self.dfa.dead))?;
+        return Ok(());
+    }
+
+    // The Group type, a replay of NFA::captures, and a tagged NFA per rule.
+    fn write_captures(&mut self) -> Result<(), Box<dyn Error>> {
+        self.write_vec(&[
+            "/// A capture group of a token and its byte span in the token's text.",
+            "#[derive(Debug, PartialEq, Eq)]",
+            "pub struct Group {",
+            "    pub name: &'static str,",
+            "    pub span: Option<(usize, usize)>",
+            "}",
+            "// Tagged NFA of a rule: per state, its tags (2g opens group g and",
+            "// 2g + 1 closes it), epsilon edges in priority order, and byte edges.",
+            "struct Tagged {",
+            "    groups:  &'static [&'static str],",
+            "    tags:    &'static [&'static [usize]],",
+            "    eps:     &'static [&'static [usize]],",
+            "    edges:   &'static [&'static [(u8, u8, usize)]],",
+            "    accepts: &'static [bool]",
+            "}",
+            "type Thread = (usize, Vec<Option<usize>>);",
+            "fn add_thread(nfa: &Tagged, threads: &mut Vec<Thread>, mark: &mut [usize],",
+            "    s: usize, pos: usize, mut slots: Vec<Option<usize>>) {",
+            "    if mark[s] == pos { return; }",
+            "    mark[s] = pos;",
+            "    for tag in nfa.tags[s] { slots[*tag] = Some(pos); }",
+            "    for nbr in nfa.eps[s] { add_thread(nfa, threads, mark, *nbr, pos, slots.clone()); }",
+            "    threads.push((s, slots));",
+            "}",
+            "fn groups(nfa: &Tagged, text: &[u8]) -> Vec<Group> {",
+            "    let mut mark = vec![usize::MAX; nfa.tags.len()];",
+            "    let mut threads: Vec<Thread> = Vec::new();",
+            "    add_thread(nfa, &mut threads, &mut mark, 0, 0, vec![None; 2 * nfa.groups.len()]);",
+            "    for (pos, c) in text.iter().enumerate() {",
+            "        let mut next: Vec<Thread> = Vec::new();",
+            "        for (s, slots) in &threads {",
+            "            for (lo, hi, t) in nfa.edges[*s] {",
+            "                if lo <= c && c <= hi {",
+            "                    add_thread(nfa, &mut next, &mut mark, *t, pos + 1, slots.clone());",
+            "                }",
+            "            }",
+            "        }",
+            "        threads = next;",
+            "    }",
+            "    let slots = threads.into_iter()",
+            "        .find(|(s, _)| nfa.accepts[*s])",
+            "        .map(|(_, slots)| slots)",
+            "        .unwrap_or(vec![None; 2 * nfa.groups.len()]);",
+            "    return nfa.groups.iter().enumerate()",
+            "        .map(|(g, name)| Group {",
+            "            name,",
+            "            span: slots[2 * g].zip(slots[2 * g + 1])",
+            "        })",
+            "        .collect();",
+            "}",
+        ])?;
+        for (idx, nfa) in self.captures.iter().enumerate() {
+            let Some(nfa) = nfa else { continue };
+            let list = |items: Vec<String>| format!("&[{}]", items.join(", "));
+            let groups: Vec<String> = nfa.groups.iter().map(|g| format!("{g:?}")).collect();
+            let tags: Vec<String> = nfa.tags.iter()
+                .map(|t| list(t.iter().map(|x| x.to_string()).collect()))
+                .collect();
+            let eps: Vec<String> = nfa.eps.iter()
+                .map(|e| list(e.iter().map(|x| x.to_string()).collect()))
+                .collect();
+            let edges: Vec<String> = nfa.jumps.iter()
+                .map(|es| list(es.iter()
+                    .map(|e| format!("({}, {}, {})", e.lo, e.hi, e.to))
+                    .collect()))
+                .collect();
+            let accepts: Vec<String> = nfa.accepts.iter().map(|a| (*a != 0).to_string()).collect();
+            self.write_vec(&[
+                &format!("// {}", self.dfa.labels[idx]),
+                &format!("const TAGGED_{idx}: Tagged = Tagged {{"),
+                &format!("    groups:  {},", list(groups)),
+                &format!("    tags:    {},", list(tags)),
+                &format!("    eps:     {},", list(eps)),
+                &format!("    edges:   {},", list(edges)),
+                &format!("    accepts: {}", list(accepts)),
+                "};",
+            ])?;
         }
-        self.writeln(&format!("_ => {}", self.dfa.dead))?;
-        self.unindent();
-        self.writeln("},")?;
         return Ok(());
     }
 
+    // A pattern matching the byte `c`.
+    fn lit(&self, c: u8) -> String {
+        return match c {
+            b'\n' => "b'\\n'".to_string(),
+            b'\t' => "b'\\t'".to_string(),
+            b'\r' => "b'\\r'".to_string(),
+            b'\\' | b'\'' => format!("b'\\{}'", c as char),
+            0x20..=0x7E => format!("b'{}'", c as char),
+            _ => format!("0x{:02X}", c)
+        };
+    }
+
     fn gen_accepts(&self) -> String {
         let mut res = "\t\tlet accepts = [\n".to_string();
         let mut idx = 0;
@@ -257,7 +388,7 @@ impl<'a> Generator<'a> {
             res.push_str(&format!("\t\t\t{:>4},\n", self.dfa.accepts[idx]));
             idx += 1;
         }
-        if self.dfa.ncount%5 > 0 {
+        if !self.dfa.ncount.is_multiple_of(5) {
             for _ in 0..(self.dfa.ncount%5 - 1) {
                 res.push_str(&format!("\t\t\t{:>4}, ", self.dfa.accepts[idx]));
                 idx += 1
@@ -269,18 +400,6 @@ impl<'a> Generator<'a> {
     }
 }
 
-fn escape(c: char) -> String {
-    match c {
-        '\n' => return "\\n".to_string(),
-        '\t' => return "\\t".to_string(),
-        '\\' => "\\\\".to_string(),
-        '\'' => "\\'".to_string(),
-        '"' => "\\\"".to_string(),
-        '\r' => "\\r".to_string(),
-        _ => return c.to_string()
-    }
-}
-
 /* TODO - Lexing Testcases... */
 #[cfg(test)]
 mod tests {
@@ -292,7 +411,7 @@ mod tests {
         let path = "example2.tk";
         let lexer = Lexer::new(path).expect("Invalid Path");
         let mut parser = Parser::new(lexer).expect("File should be non-empty!");
-        let nfa = NFA::build_from_matches(&parser.parse().expect("Invalid parse"));
+        let nfa = NFA::build_from_matches(&parser.parse().expect("Invalid parse")).unwrap();
         let dfa = DFA::compress(DFA::subset_construction(nfa));
         let mut gen = Generator::new(&dfa, "tests/tokenizer.rs".to_string())
             .expect("Just Be Better");
@@ -0,0 +1,271 @@
+use std::{cell::RefCell, collections::HashMap, error::Error, mem::size_of};
+use crate::ast::Match;
+use crate::dfa::ByteClasses;
+use crate::lexer::Span;
+use crate::nfa::BuildError;
+use crate::parser;
+use crate::pikevm::{PikeVM, Token, LexError};
+
+// A transition not determinized yet.
+const UNKNOWN: usize = usize::MAX;
+// The transition into the empty set.
+const DEAD: usize = usize::MAX - 1;
+// A cache that is cleared before it has served this many bytes per
+// state it held is thrashing.
+const MIN_BYTES_PER_STATE: usize = 10;
+// Clears in a row that thrash before giving up on the cache.
+const MAX_THRASHES: usize = 3;
+
+/// Tokenizes input like `PikeVM`, but determinizes the NFA as it goes:
+/// each set of NFA states the input reaches becomes a DFA state, cached
+/// with its transitions so later tokens run at DFA speed. Only states
+/// the input needs are built, so specs whose full DFA would be huge
+/// stay cheap. The cache is cleared when it outgrows its limit, and if
+/// that keeps happening the lexer falls back to simulating the NFA.
+pub struct LazyDFA {
+    vm: PikeVM,
+    classes: ByteClasses,
+    cache: RefCell<Cache>
+}
+
+// The determinized states. `trans[s * stride + k]` is where state s
+// goes on class k.
+struct Cache {
+    sets: Vec<Vec<usize>>,
+    ids: HashMap<Vec<usize>, usize>,
+    trans: Vec<usize>,
+    accepts: Vec<usize>,
+    // Memory used by the states above, and the most they may use.
+    bytes: usize,
+    limit: usize,
+    // Bytes scanned since the last clear.
+    scanned: usize,
+    clears: usize,
+    thrashes: usize,
+    fallback: bool
+}
+
+impl LazyDFA {
+    /// The default cache limit, in bytes.
+    pub const CACHE_LIMIT: usize = 1 << 21;
+
+    pub fn new(matches: &[Match]) -> Result<Self, BuildError> {
+        let vm = PikeVM::new(matches)?;
+        let classes = vm.nfa().byte_classes();
+        let cache = Cache {
+            sets: Vec::new(),
+            ids: HashMap::new(),
+            trans: Vec::new(),
+            accepts: Vec::new(),
+            bytes: 0,
+            limit: LazyDFA::CACHE_LIMIT,
+            scanned: 0,
+            clears: 0,
+            thrashes: 0,
+            fallback: false
+        };
+        return Ok(LazyDFA { vm, classes, cache: RefCell::new(cache) });
+    }
+
+    pub fn from_spec(src: &str) -> Result<Self, Box<dyn Error>> {
+        return Ok(LazyDFA::new(&parser::parse_spec(src)?)?);
+    }
+
+    /// Caps the memory the cached states may use, in bytes.
+    pub fn set_cache_limit(&mut self, limit: usize) {
+        self.cache.get_mut().limit = limit;
+    }
+
+    /// The number of states cached now.
+    pub fn cached(&self) -> usize {
+        return self.cache.borrow().sets.len();
+    }
+
+    /// Whether the cache thrashed and tokens now come from the NFA.
+    pub fn is_fallback(&self) -> bool {
+        return self.cache.borrow().fallback;
+    }
+
+    /// The tokens of `input`, as `PikeVM::tokens` gives them.
+    pub fn tokens<'a>(&'a self, input: &'a [u8]) -> Tokens<'a> {
+        return Tokens { lazy: self, input, pos: 0, failed: false };
+    }
+
+    /// The capture groups of `token`, see `PikeVM::captures`.
+    pub fn captures(&self, input: &[u8], token: &Token) -> Vec<(&str, Option<Span>)> {
+        return self.vm.captures(input, token);
+    }
+
+    /// The longest match starting at `start`, as its end and rule.
+    pub fn longest(&self, input: &[u8], start: usize) -> Option<(usize, usize)> {
+        let mut cache = self.cache.borrow_mut();
+        if cache.fallback { return self.vm.longest(input, start); }
+        let stride = self.classes.len();
+        let mut state = self.state(&mut cache, vec![0]);
+        let mut best = None;
+        let mut pos = start;
+        loop {
+            let accept = cache.accepts[state];
+            if accept != 0 { best = Some((pos, accept)); }
+            if pos == input.len() { break; }
+            let at = state * stride + self.classes.get(input[pos]);
+            let mut next = cache.trans[at];
+            if next == UNKNOWN {
+                let set = self.step(&cache, state, input[pos]);
+                if set.is_empty() {
+                    next = DEAD;
+                    cache.trans[at] = DEAD;
+                } else {
+                    let clears = cache.clears;
+                    next = self.state(&mut cache, set);
+                    if cache.fallback { return self.vm.longest(input, start); }
+                    // A clear dropped `state` along with its row.
+                    if cache.clears == clears { cache.trans[at] = next; }
+                }
+            }
+            if next == DEAD { break; }
+            state = next;
+            pos += 1;
+            cache.scanned += 1;
+        }
+        return best;
+    }
+
+    // The NFA states reached from the states of `state` on byte `c`.
+    fn step(&self, cache: &Cache, state: usize, c: u8) -> Vec<usize> {
+        let nfa = self.vm.nfa();
+        let mut set: Vec<usize> = cache.sets[state].iter()
+            .flat_map(|s| nfa.next(*s, c))
+            .collect();
+        set.sort_unstable();
+        set.dedup();
+        return set;
+    }
+
+    // The cached state for `set`, added on first sight. Clears the
+    // cache first if the new state would not fit.
+    fn state(&self, cache: &mut Cache, set: Vec<usize>) -> usize {
+        if let Some(id) = cache.ids.get(&set) { return *id; }
+        let stride = self.classes.len();
+        let cost = (2 * set.len() + stride + 4) * size_of::<usize>();
+        if cache.bytes + cost > cache.limit && !cache.sets.is_empty() {
+            cache.clear();
+        }
+        let nfa = self.vm.nfa();
+        let accept = set.iter()
+            .map(|s| nfa.accepts[*s])
+            .filter(|acc| *acc != 0)
+            .min()
+            .unwrap_or(0);
+        let id = cache.sets.len();
+        cache.ids.insert(set.clone(), id);
+        cache.sets.push(set);
+        cache.trans.extend(std::iter::repeat_n(UNKNOWN, stride));
+        cache.accepts.push(accept);
+        cache.bytes += cost;
+        return id;
+    }
+}
+
+impl Cache {
+    // Empties the cache, and gives up on it if it keeps filling up
+    // before it has paid for itself.
+    fn clear(&mut self) {
+        if self.scanned < MIN_BYTES_PER_STATE * self.sets.len() {
+            self.thrashes += 1;
+        } else {
+            self.thrashes = 0;
+        }
+        if self.thrashes >= MAX_THRASHES { self.fallback = true; }
+        self.sets.clear();
+        self.ids.clear();
+        self.trans.clear();
+        self.accepts.clear();
+        self.bytes = 0;
+        self.scanned = 0;
+        self.clears += 1;
+    }
+}
+
+pub struct Tokens<'a> {
+    lazy: &'a LazyDFA,
+    input: &'a [u8],
+    pos: usize,
+    failed: bool
+}
+
+impl<'a> Iterator for Tokens<'a> {
+    type Item = Result<Token<'a>, LexError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed { return None; }
+        self.pos = self.lazy.vm.skip(self.input, self.pos);
+        if self.pos == self.input.len() { return None; }
+        let start = self.pos;
+        match self.lazy.longest(self.input, start) {
+            Some((end, acc)) if end > start => {
+                self.pos = end;
+                let name = self.lazy.vm.label(acc - 1);
+                return Some(Ok(Token { name, rule: acc - 1, span: Span { start, end } }));
+            },
+            _ => {
+                self.failed = true;
+                return Some(Err(LexError { pos: start }));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::Path};
+    use super::*;
+
+    fn same_tokens(lazy: &LazyDFA, input: &[u8]) -> bool {
+        let vm = &lazy.vm;
+        return lazy.tokens(input).eq(vm.tokens(input));
+    }
+
+    #[test]
+    fn tester_data() {
+        let path = "tests/tester/data";
+        let mut i = 0;
+        while Path::new(&format!("{path}/gen-{i}.tk")).exists() {
+            let src = fs::read_to_string(format!("{path}/gen-{i}.tk")).unwrap();
+            let lazy = LazyDFA::from_spec(&src).expect("Valid spec");
+            let input = fs::read(format!("{path}/in-{i}.txt")).unwrap();
+            assert!(same_tokens(&lazy, &input), "gen-{i}");
+            assert!(!lazy.is_fallback() && lazy.cached() > 0);
+            i += 1;
+        }
+        let mut i = 0;
+        while Path::new(&format!("{path}/ws-{i}.tk")).exists() {
+            let src = fs::read_to_string(format!("{path}/ws-{i}.tk")).unwrap();
+            let lazy = LazyDFA::from_spec(&src).expect("Valid spec");
+            let input = fs::read(format!("{path}/ws-in-{i}.txt")).unwrap();
+            assert!(same_tokens(&lazy, &input), "ws-{i}");
+            i += 1;
+        }
+    }
+
+    #[test]
+    fn bounded_cache() {
+        // The full DFA needs a state per suffix of 12 bytes.
+        let spec = "\"(a|b)*a(a|b)(a|b)(a|b)(a|b)(a|b)(a|b)(a|b)(a|b)(a|b)(a|b)(a|b)\" X; \" \" ;";
+        let mut input: Vec<u8> = Vec::new();
+        let mut seed: u32 = 7;
+        for i in 0..4000 {
+            seed = seed.wrapping_mul(1103515245).wrapping_add(12345);
+            input.push(if (seed >> 16) & 1 == 0 { b'a' } else { b'b' });
+            if i % 500 == 499 { input.push(b' '); }
+        }
+        let lazy = LazyDFA::from_spec(spec).unwrap();
+        assert!(same_tokens(&lazy, &input) && !lazy.is_fallback());
+
+        let mut small = LazyDFA::from_spec(spec).unwrap();
+        small.set_cache_limit(16 * 1024);
+        assert!(same_tokens(&small, &input) && small.is_fallback());
+        assert!(small.cached() < lazy.cached());
+        assert!(same_tokens(&small, b"ab ba aab"));
+    }
+}
@@ -1,6 +1,19 @@
+#![allow(clippy::needless_return)]
 pub mod ast;
+pub mod derivative;
 pub mod dfa;
+pub mod direct;
+pub mod export;
 pub mod generator;
+pub mod lazy;
 pub mod lexer;
 pub mod nfa;
-pub mod parser;
\ No newline at end of file
+pub mod ops;
+pub mod parser;
+pub mod pikevm;
+pub mod printer;
+pub mod simplify;
+pub mod unicode;
+mod unicode_tables;
+pub mod utf8;
+pub mod visit;
\ No newline at end of file
@@ -1,21 +1,175 @@
-use rflex_lib::{lexer::Lexer, parser::Parser, nfa::NFA, dfa::DFA, generator::Generator};
-use std::env;
+#![allow(clippy::needless_return)]
+use rflex_lib::{lexer::Lexer, parser::Parser, nfa::{NFA, Encoding, BuildError}, generator::Generator};
+use rflex_lib::dfa::{DFA, Limits};
+use rflex_lib::ast::{self, Match};
+use rflex_lib::{derivative, direct, export, lazy::LazyDFA, parser, pikevm::PikeVM, printer, simplify};
+use std::{env, error::Error, fs, io::{self, Read}, time::{Duration, Instant}};
 fn main() {
     let args: Vec<String> = env::args().collect();
+    match args.get(1).map(|s| s.as_str()) {
+        Some("fmt") => fmt(&args[2..]),
+        Some("ast") => ast(&args[2..]),
+        Some("lex") => lex(&args[2..]),
+        _ => generate(&args[1..])
+    }
+}
+
+fn generate(args: &[String]) {
+    let (flags, args): (Vec<&String>, Vec<&String>) = args.iter()
+        .partition(|s| s.starts_with("--"));
     let mut inpath = "example.tk".to_string();
-    if let Some(s) = args.get(1) {
-        inpath = s.clone();
+    if let Some(s) = args.first() {
+        inpath = s.to_string();
     }
     let mut outpath = "tokenizer.rs".to_string();
-    if let Some(s) = args.get(2) {
-        outpath = s.clone();
+    if let Some(s) = args.get(1) {
+        outpath = s.to_string();
     }
-    let lexer = Lexer::new(&inpath).expect("Invalid Path");
+    let lexer = Lexer::from(read_spec(&inpath));
     let mut parser = Parser::new(lexer).expect("File should be non-empty!");
-    let nfa = NFA::build_from_matches(&parser.parse().expect("Invalid parse"));
-    //nfa.print_dot();
-    let dfa = DFA::compress(DFA::subset_construction(nfa));
+    let mut matches = parser.parse().expect("Invalid parse");
+    let before: Vec<String> = matches.iter().map(|m| m.root.to_string()).collect();
+    simplify::simplify_matches(&mut matches);
+    if flags.iter().any(|f| *f == "--dump-simplify") {
+        for (m, before) in matches.iter().zip(before) {
+            eprintln!("{}\nbefore:\n{}after:\n{}", m.name, before, m.root);
+        }
+    }
+    let binary = flags.iter().any(|f| *f == "--binary");
+    let encoding = if binary { Encoding::Latin1 } else { Encoding::Utf8 };
+    let construction = flags.iter()
+        .find_map(|f| f.strip_prefix("--construction="))
+        .unwrap_or("thompson");
+    let limits = limits(&flags);
+    let start = Instant::now();
+    let dfa = match construction {
+        "thompson" => {
+            let nfa = or_exit(NFA::build_with_limits(&matches, encoding, &limits), &matches);
+            //nfa.print_dot();
+            or_exit(DFA::subset_construction_with(nfa, &limits), &matches)
+        },
+        "direct" => or_exit(direct::build_from_matches_with(&matches, encoding, &limits), &matches),
+        "derivative" => or_exit(derivative::build_from_matches_with(&matches, encoding, &limits), &matches),
+        _ => panic!("Unknown construction {construction}, expected thompson, direct or derivative")
+    };
+    let built = start.elapsed();
+    let states = dfa.ncount;
+    let dfa = DFA::compress(dfa);
     //dfa.print_dot();
+    if flags.iter().any(|f| *f == "--stats") {
+        eprintln!("{construction}: {states} states in {built:?}, {} after compression in {:?}",
+            dfa.ncount, start.elapsed() - built
+        );
+    }
+    let captures: Vec<Option<NFA>> = ast::priority_order(&matches).into_iter()
+        .map(|i| &matches[i])
+        .filter(|m| !m.name.is_empty())
+        .map(|m| if m.root.groups().is_empty() {
+            None
+        } else {
+            Some(or_exit(NFA::build_rule(m, encoding), &matches))
+        })
+        .collect();
     let mut gen = Generator::new(&dfa, outpath).expect("Outpath doesn't exist");
+    gen.set_binary(binary);
+    gen.set_captures(&captures);
     gen.generate().expect("Write Error");
-}
\ No newline at end of file
+}
+
+fn fmt(args: &[String]) {
+    let inpath = args.first().expect("Usage: rflex fmt [input_file]");
+    let src = read_spec(inpath);
+    print!("{}", printer::format_spec(&src).expect("Invalid parse"));
+}
+
+fn ast(args: &[String]) {
+    let usage = "Usage: rflex ast [--format json|xml] [input_file]";
+    let mut format = "json";
+    let mut inpath: Option<&String> = None;
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "--format" => format = args.next().expect(usage),
+            _ => inpath = Some(arg)
+        }
+    }
+    let src = read_spec(inpath.expect(usage));
+    let matches = parser::parse_spec(&src).expect("Invalid parse");
+    match format {
+        "json" => print!("{}", export::to_json(&matches)),
+        "xml"  => print!("{}", export::to_xml(&matches)),
+        _ => panic!("{}", usage)
+    }
+}
+
+fn lex(args: &[String]) {
+    let usage = "Usage: rflex lex [--lazy] [spec_file] [input_file]";
+    let (flags, args): (Vec<&String>, Vec<&String>) = args.iter()
+        .partition(|s| s.starts_with("--"));
+    let (Some(spec), Some(inpath)) = (args.first(), args.get(1)) else { panic!("{}", usage) };
+    let matches = parser::parse_spec(&read_spec(spec)).expect("Invalid parse");
+    let input = fs::read(inpath).expect("Invalid Path");
+    let (vm, lazy);
+    let tokens: Box<dyn Iterator<Item = _>> = if flags.iter().any(|f| *f == "--lazy") {
+        lazy = or_exit(LazyDFA::new(&matches), &matches);
+        Box::new(lazy.tokens(&input))
+    } else {
+        vm = or_exit(PikeVM::new(&matches), &matches);
+        Box::new(vm.tokens(&input))
+    };
+    for tk in tokens {
+        match tk {
+            Ok(tk) => println!("{}..{} {} {:?}", tk.span.start, tk.span.end, tk.name,
+                String::from_utf8_lossy(&input[tk.span.start..tk.span.end])
+            ),
+            Err(err) => {
+                eprintln!("{err}");
+                std::process::exit(1);
+            }
+        }
+    }
+}
+
+// --max-nfa-states=N, --max-dfa-states=N and --timeout=SECS override
+// the default limits.
+fn limits(flags: &[&String]) -> Limits {
+    let mut limits = Limits::default();
+    for flag in flags {
+        let Some((name, value)) = flag.split_once('=') else { continue };
+        let value = || value.parse::<u64>()
+            .unwrap_or_else(|_| panic!("{name} expects a number but got {value}"));
+        match name {
+            "--max-nfa-states" => limits.nfa_states = value() as usize,
+            "--max-dfa-states" => limits.dfa_states = value() as usize,
+            "--timeout" => limits.time = Duration::from_secs(value()),
+            _ => {}
+        }
+    }
+    return limits;
+}
+
+// Reports a failed construction, naming the rule a BuildError is for,
+// and exits.
+fn or_exit<T, E: Into<Box<dyn Error>>>(result: Result<T, E>, matches: &[Match]) -> T {
+    return result.unwrap_or_else(|err| {
+        let err = err.into();
+        match err.downcast_ref::<BuildError>() {
+            Some(build) => {
+                let name = &matches[build.rule()].name;
+                eprintln!("{err} ({})", if name.is_empty() { "whitespace" } else { name });
+            },
+            None => eprintln!("{err}")
+        }
+        std::process::exit(1);
+    });
+}
+
+// "-" reads the specification from stdin.
+fn read_spec(inpath: &str) -> String {
+    if inpath == "-" {
+        let mut src = String::new();
+        io::stdin().read_to_string(&mut src).expect("Invalid Input");
+        return src;
+    }
+    return fs::read_to_string(inpath).expect("Invalid Path");
+}
@@ -1,13 +1,96 @@
-use crate::{ast::{self, Match}, lexer};
+use std::{error::Error, fmt};
+use crate::{ast::{self, Match}, dfa::{ByteClasses, DFA, Limit, Limits}, lexer::{self, Span}, utf8};
+use crate::visit::{self, Visitor};
+use crate::unicode::Property;
+
+/// How characters in the spec become bytes in the automata.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub enum Encoding {
+    /// A character matches its UTF-8 encoding.
+    Utf8,
+    /// U+0000 to U+00FF match the byte of the same value, for binary input.
+    Latin1
+}
+
+/// Why a rule could not be compiled. `rule` indexes the matches the
+/// NFA was built from.
+#[derive(Debug, PartialEq, Eq)]
+pub enum BuildError {
+    /// Whitespace rules may only alternate characters and classes.
+    Whitespace { rule: usize },
+    /// A binary node with a unary op, or the other way around.
+    UnexpectedOp { rule: usize, op: lexer::Op },
+    /// A DASH node whose ends are not both characters.
+    InvalidRange { rule: usize },
+    /// A character above U+00FF in a binary (Latin-1) lexer.
+    OutOfRange { rule: usize, c: char }
+}
+impl BuildError {
+    pub fn rule(&self) -> usize {
+        return match self {
+            BuildError::Whitespace { rule } |
+            BuildError::UnexpectedOp { rule, .. } |
+            BuildError::InvalidRange { rule } |
+            BuildError::OutOfRange { rule, .. } => *rule
+        };
+    }
+}
+impl fmt::Display for BuildError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return match self {
+            BuildError::Whitespace { rule } =>
+                write!(f, "Rule {rule}: White Space should be single or-seperated tokens"),
+            BuildError::UnexpectedOp { rule, op } =>
+                write!(f, "Rule {rule}: Unexpected op {op:?}"),
+            BuildError::InvalidRange { rule } =>
+                write!(f, "Rule {rule}: Range ends should be characters"),
+            BuildError::OutOfRange { rule, c } =>
+                write!(f, "Rule {rule}: {c:?} is out of range for a binary lexer")
+        };
+    }
+}
+impl std::error::Error for BuildError {}
+
+/// The ranges of `prop`. Binary lexers only see its Latin-1 part.
+pub(crate) fn property_ranges(prop: Property, encoding: Encoding) -> Vec<(char, char)> {
+    let mut ranges = prop.ranges();
+    if encoding == Encoding::Latin1 {
+        ranges.retain(|(lo, _)| *lo <= '\u{FF}');
+        for r in ranges.iter_mut() { r.1 = r.1.min('\u{FF}'); }
+    }
+    return ranges;
+}
+
+/// Reading any byte in `lo..=hi` moves to state `to`.
+#[derive(Copy, Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Edge {
+    pub lo: u8,
+    pub hi: u8,
+    pub to: usize
+}
 
-const NULL: usize = usize::MAX;
 pub struct NFA { 
     pub ncount:  usize,
-    pub jumps:   Vec<[usize; u8::MAX as usize]>,
+    pub jumps:   Vec<Vec<Edge>>,
     pub eps:     Vec<Vec<usize>>,
     pub accepts: Vec<usize>,
-    pub labels:  Vec<String>
+    pub labels:  Vec<String>,
+    pub encoding: Encoding,
+    /// The accept id of the rule each state was built for, or 0 for the
+    /// start state and whitespace.
+    pub owners:  Vec<usize>,
+    // The rule being built, for errors.
+    rule:        usize,
+    /// Tags recorded on reaching each state: 2g opens group g, 2g + 1 closes it.
+    pub tags:    Vec<Vec<usize>>,
+    pub groups:  Vec<String>
+}
+impl Default for NFA {
+    fn default() -> Self {
+        return NFA::new();
+    }
 }
+
 impl NFA {
     pub fn new() -> Self {
         return NFA { 
@@ -15,77 +98,124 @@ impl NFA {
             jumps:   Vec::new(),
             eps:     Vec::new(),
             accepts: Vec::new(),
-            labels:  Vec::new()
+            labels:  Vec::new(),
+            encoding: Encoding::Utf8,
+            owners:  Vec::new(),
+            rule:    0,
+            tags:    Vec::new(),
+            groups:  Vec::new()
         };
     }
 
-    pub fn build_from_matches(matches: &Vec<ast::Match>) -> Self {
+    pub fn build_from_matches(matches: &[ast::Match]) -> Result<Self, BuildError> {
+        return NFA::build_with_encoding(matches, Encoding::Utf8);
+    }
+
+    pub fn build_with_encoding(matches: &[ast::Match], encoding: Encoding)
+        -> Result<Self, BuildError> {
         let mut nfa = NFA::new();
-        let root = nfa.make_node();
-        for m in matches {
-            if m.name.len() == 0 {
-                nfa.handle_whitespace(&m.root);
-            } else {
-                let node = NFA::build_ast(&mut nfa, m);
-                nfa.add_eps(root,node);
+        nfa.encoding = encoding;
+        nfa.make_node();
+        for rule in ast::priority_order(matches) {
+            nfa.add_rule(&matches[rule], rule)?;
+        }
+        return Ok(nfa);
+    }
+
+    /// `build_with_encoding`, giving up once the NFA has more than
+    /// `limits.nfa_states` states, which is checked after each rule.
+    pub fn build_with_limits(matches: &[ast::Match], encoding: Encoding, limits: &Limits)
+        -> Result<Self, Box<dyn Error>> {
+        let mut nfa = NFA::new();
+        nfa.encoding = encoding;
+        nfa.make_node();
+        for rule in ast::priority_order(matches) {
+            nfa.add_rule(&matches[rule], rule)?;
+            if nfa.ncount > limits.nfa_states {
+                let sets: Vec<Vec<usize>> = (0..nfa.ncount).map(|s| vec![s]).collect();
+                return Err(Box::new(DFA::blame(&nfa, &sets, Limit::NfaStates(limits.nfa_states))));
             }
         }
-        return nfa;
+        return Ok(nfa);
     }
 
-    fn handle_whitespace(&mut self, ast: &ast::Node) {
-        match ast {
-            ast::Node::BinaryExpr(_) => {
-                let mut stk: Vec<&ast::Node> = vec![ast; 1];
-                while let Some(state) = stk.pop() {
-                    match state {
-                        ast::Node::BinaryExpr(node) => {
-                            if let lexer::Op::BAR = node.op {
-                                stk.push(&node.left);
-                                stk.push(&node.right);
-                            } else {
-                                panic!("You used something besides or in whitespace!");
-                            }
-                        },
-                        ast::Node::Char(c) => self.add(0, 0, *c),
-                        _ => panic!("Neither Char no Or in whitespace!")
-                    }
-                }
-            },
-            ast::Node::Char(c) => self.add(0, 0, *c),
-            _ => panic!("White Space should be single or-seperated tokens!")
+    // Adds `m`, the rule at index `rule`, from the root.
+    fn add_rule(&mut self, m: &Match, rule: usize) -> Result<(), BuildError> {
+        self.rule = rule;
+        if m.name.is_empty() {
+            return self.handle_whitespace(&m.root);
+        }
+        let first = self.ncount;
+        let node = NFA::build_ast(self, m)?;
+        self.add_eps(0, node);
+        let owner = self.labels.len();
+        for o in &mut self.owners[first..] { *o = owner; }
+        return Ok(());
+    }
+
+    /// The NFA of the single rule `m`, with its capture groups tagged.
+    pub fn build_rule(m: &Match, encoding: Encoding) -> Result<Self, BuildError> {
+        let mut nfa = NFA::new();
+        nfa.encoding = encoding;
+        let root = nfa.make_node();
+        let node = NFA::build_ast(&mut nfa, m)?;
+        nfa.add_eps(root, node);
+        return Ok(nfa);
+    }
+
+    fn handle_whitespace(&mut self, ast: &ast::Node) -> Result<(), BuildError> {
+        let mut ws = Whitespace { ranges: Vec::new(), props: Vec::new(), valid: true };
+        ws.visit_node(ast);
+        if !ws.valid {
+            return Err(BuildError::Whitespace { rule: self.rule });
         }
+        for prop in ws.props {
+            ws.ranges.extend(property_ranges(prop, self.encoding));
+        }
+        return self.add_class(0, 0, &ws.ranges);
     }
 
-    fn build_ast(nfa: &mut NFA, m: &Match) -> usize {
-        let (start, end) = nfa.build(&m.root);
+    fn build_ast(nfa: &mut NFA, m: &Match) -> Result<usize, BuildError> {
+        let (start, end) = nfa.build(&m.root)?;
         nfa.label(end, m.name.clone());
-        return start;
+        return Ok(start);
     }
 
 
-    fn build(&mut self, ast: &ast::Node) -> (usize, usize) {
+    fn build(&mut self, ast: &ast::Node) -> Result<(usize, usize), BuildError> {
         return match ast {
             ast::Node::BinaryExpr(node) => {
-                let left = self.build(&node.left);
-                let right = self.build(&node.right);
+                if node.op == lexer::Op::DASH {
+                    let (ast::Node::Char(lo), ast::Node::Char(hi)) = (&*node.left, &*node.right)
+                    else { return Err(BuildError::InvalidRange { rule: self.rule }) };
+                    return self.handle_dash(*lo, *hi);
+                }
+                let left = self.build(&node.left)?;
+                let right = self.build(&node.right)?;
                 match node.op {
-                    lexer::Op::BAR  => self.handle_bar(left, right),
-                    lexer::Op::DASH => self.handle_dash(node.left.char(), node.right.char()),
-                    lexer::Op::AND  => self.handle_add(left, right),
-                    _ => panic!("Expected Binary Op but got {:?}", node.op)
+                    lexer::Op::BAR  => Ok(self.handle_bar(left, right)),
+                    lexer::Op::AND  => Ok(self.handle_add(left, right)),
+                    op => Err(BuildError::UnexpectedOp { rule: self.rule, op })
                 }
             },
             ast::Node::UnaryExpr(node) => {
-                let child = self.build(&node.child);
+                let child = self.build(&node.child)?;
                 match node.op {
-                    lexer::Op::STAR     => self.handle_star(child),
-                    lexer::Op::PLUS     => self.handle_plus(child),
-                    lexer::Op::QUESTION => self.handle_question(child),
-                    _ => panic!("Expected Unary Op but got {:?}", node.op)
+                    lexer::Op::STAR     => Ok(self.handle_star(child)),
+                    lexer::Op::PLUS     => Ok(self.handle_plus(child)),
+                    lexer::Op::QUESTION => Ok(self.handle_question(child)),
+                    op => Err(BuildError::UnexpectedOp { rule: self.rule, op })
                 }
             },
-            ast::Node::Char(c) => self.handle_char(*c)
+            ast::Node::Class(ranges) => self.handle_class(ranges),
+            ast::Node::Property(p) => self.handle_property(*p),
+            ast::Node::Char(c) => self.handle_char(*c),
+            ast::Node::Capture(node) => {
+                let group = self.groups.len();
+                self.groups.push(node.name.clone());
+                let child = self.build(&node.child)?;
+                Ok(self.handle_capture(child, group))
+            }
         }
     }
 
@@ -100,13 +230,11 @@ impl NFA {
         return (i, f);
     }
 
-    fn handle_dash(&mut self, start: char, end: char) -> (usize, usize) {
+    fn handle_dash(&mut self, start: char, end: char) -> Result<(usize, usize), BuildError> {
         let i = self.make_node();
         let f = self.make_node();
-        for c in start..=end { 
-            self.add(i, f, c);
-        }
-        return (i, f);
+        self.add(i, f, start, end)?;
+        return Ok((i, f));
     }
 
     fn handle_add(&mut self, left: (usize, usize), right: (usize, usize)) 
@@ -114,9 +242,23 @@ impl NFA {
         let (_, lf) = left;
         let (ri, _) = right;
         self.swap(lf, ri);
+        // lf now stands in for ri, so it records ri's tags after its own.
+        let tags = std::mem::take(&mut self.tags[ri]);
+        self.tags[lf].extend(tags);
         return (left.0, right.1);
     }
 
+    fn handle_capture(&mut self, child: (usize, usize), group: usize) -> (usize, usize) {
+        let (start, end) = child;
+        let i = self.make_node();
+        let f = self.make_node();
+        self.tags[i].push(2 * group);
+        self.tags[f].push(2 * group + 1);
+        self.add_eps(i, start);
+        self.add_eps(end, f);
+        return (i, f);
+    }
+
     fn handle_question(&mut self, child: (usize, usize)) -> (usize, usize) {
         let (start, end) = child;
         let i = self.make_node();
@@ -148,11 +290,22 @@ impl NFA {
         return (i, f);
     }
 
-    fn handle_char(&mut self, c: char) -> (usize, usize) {
+    fn handle_class(&mut self, ranges: &[(char, char)]) -> Result<(usize, usize), BuildError> {
         let i = self.make_node();
         let f = self.make_node();
-        self.add(i, f, c);
-        return (i, f);
+        self.add_class(i, f, ranges)?;
+        return Ok((i, f));
+    }
+
+    fn handle_property(&mut self, prop: Property) -> Result<(usize, usize), BuildError> {
+        return self.handle_class(&property_ranges(prop, self.encoding));
+    }
+
+    fn handle_char(&mut self, c: char) -> Result<(usize, usize), BuildError> {
+        let i = self.make_node();
+        let f = self.make_node();
+        self.add(i, f, c, c)?;
+        return Ok((i, f));
     }
 
     fn label(&mut self, i: usize, label: String) {
@@ -169,15 +322,171 @@ impl NFA {
         self.eps[i].push(f);
     }
 
-    fn add(&mut self, i: usize, f: usize, c: char) {
-        self.jumps[i][c as usize] = f;
+    fn add(&mut self, i: usize, f: usize, lo: char, hi: char) -> Result<(), BuildError> {
+        return self.add_class(i, f, &[(lo, hi)]);
+    }
+
+    fn add_class(&mut self, i: usize, f: usize, ranges: &[(char, char)]) -> Result<(), BuildError> {
+        if self.encoding == Encoding::Latin1 {
+            let rule = self.rule;
+            let byte = |c: char| u8::try_from(c).map_err(|_| BuildError::OutOfRange { rule, c });
+            for (lo, hi) in ranges {
+                if lo <= hi { self.add_edge(i, f, byte(*lo)?, byte(*hi)?); }
+            }
+            return Ok(());
+        }
+        // Sequences sharing leading byte ranges share the states between them.
+        let mut made: Vec<usize> = Vec::new();
+        let seqs = ranges.iter().flat_map(|(lo, hi)| utf8::sequences(*lo, *hi));
+        for seq in seqs {
+            let (last, init) = seq.split_last().expect("Sequences are non-empty");
+            let mut cur = i;
+            for (a, b) in init {
+                let shared = self.jumps[cur].iter()
+                    .find(|e| e.lo == *a && e.hi == *b && made.contains(&e.to))
+                    .map(|e| e.to);
+                cur = match shared {
+                    Some(next) => next,
+                    None => {
+                        let next = self.make_node();
+                        made.push(next);
+                        self.add_edge(cur, next, *a, *b);
+                        next
+                    }
+                };
+            }
+            self.add_edge(cur, f, last.0, last.1);
+        }
+        return Ok(());
+    }
+
+    // Edges stay sorted, so equal ranges end up next to each other.
+    fn add_edge(&mut self, i: usize, f: usize, lo: u8, hi: u8) {
+        let edge = Edge { lo, hi, to: f };
+        let edges = &mut self.jumps[i];
+        let at = edges.partition_point(|e| *e < edge);
+        if edges.get(at) != Some(&edge) { edges.insert(at, edge); }
+    }
+
+    /// An NFA with the same language and accept labels but no epsilon
+    /// edges. Each state takes over the edges of its closure and accepts
+    /// the winning rule accepted in it; states that were only reached
+    /// by epsilon edges are dropped, and the rest keep their order.
+    pub fn remove_eps(&self) -> NFA {
+        let mut nfa = NFA::new();
+        nfa.labels = self.labels.clone();
+        nfa.encoding = self.encoding;
+        // The start and every state a byte leads to, numbered in their
+        // original order so the states of earlier rules still come first.
+        let mut id = vec![usize::MAX; self.ncount];
+        id[0] = 0;
+        for e in self.jumps.iter().flatten() { id[e.to] = 0; }
+        let order: Vec<usize> = (0..self.ncount).filter(|s| id[*s] != usize::MAX).collect();
+        for s in &order {
+            id[*s] = nfa.make_node();
+            nfa.owners[id[*s]] = self.owners[*s];
+        }
+        // stamp[c] == s + 1 once c is in the closure of s.
+        let mut stamp = vec![0; self.ncount];
+        let mut stack: Vec<usize> = Vec::new();
+        for &s in &order {
+            stamp[s] = s + 1;
+            stack.push(s);
+            while let Some(c) = stack.pop() {
+                for nbr in &self.eps[c] {
+                    if stamp[*nbr] == s + 1 { continue; }
+                    stamp[*nbr] = s + 1;
+                    stack.push(*nbr);
+                }
+                let acc = self.accepts[c];
+                let cur = &mut nfa.accepts[id[s]];
+                if acc != 0 && (*cur == 0 || acc < *cur) { *cur = acc; }
+                for e in &self.jumps[c] {
+                    nfa.add_edge(id[s], id[e.to], e.lo, e.hi);
+                }
+            }
+        }
+        return nfa;
+    }
+
+    /// `set` and every state reachable from it by epsilon edges.
+    pub fn eps_closure(&self, set: Vec<usize>) -> Vec<usize> {
+        let mut has = vec![false; self.ncount];
+        let mut closure: Vec<usize> = set;
+        let mut stack: Vec<usize> = Vec::new();
+        for s in &closure { 
+            stack.push(*s); 
+            has[*s] = true;
+        }
+        while let Some(s) = stack.pop() {
+            for nbr in &self.eps[s] {
+                if has[*nbr] { continue; }
+                has[*nbr] = true;
+                closure.push(*nbr);
+                stack.push(*nbr);
+            }
+        }
+        return closure;
+    }
+
+    /// The span of each group when `text` is matched as a whole, or None
+    /// if it does not match. Earlier alternatives and longer repetitions
+    /// are preferred, and a group inside a loop keeps its last span.
+    pub fn captures(&self, text: &[u8]) -> Option<Vec<Option<Span>>> {
+        let mut mark = vec![usize::MAX; self.ncount];
+        let mut threads: Vec<(usize, Vec<Option<usize>>)> = Vec::new();
+        self.add_thread(&mut threads, &mut mark, 0, 0, vec![None; 2 * self.groups.len()]);
+        for (pos, c) in text.iter().enumerate() {
+            let mut next: Vec<(usize, Vec<Option<usize>>)> = Vec::new();
+            for (s, slots) in &threads {
+                for t in self.next(*s, *c) {
+                    self.add_thread(&mut next, &mut mark, t, pos + 1, slots.clone());
+                }
+            }
+            threads = next;
+        }
+        let (_, slots) = threads.into_iter().find(|(s, _)| self.accepts[*s] != 0)?;
+        return Some(slots.chunks(2)
+            .map(|g| match (g[0], g[1]) {
+                (Some(start), Some(end)) => Some(Span { start, end }),
+                _ => None
+            })
+            .collect());
+    }
+
+    // Threads are kept in priority order, so a state keeps the first
+    // thread to reach it at each position.
+    fn add_thread(&self, threads: &mut Vec<(usize, Vec<Option<usize>>)>, mark: &mut [usize],
+        s: usize, pos: usize, mut slots: Vec<Option<usize>>) {
+        if mark[s] == pos { return; }
+        mark[s] = pos;
+        for tag in &self.tags[s] { slots[*tag] = Some(pos); }
+        for nbr in &self.eps[s] {
+            self.add_thread(threads, mark, *nbr, pos, slots.clone());
+        }
+        threads.push((s, slots));
+    }
+
+    /// The byte classes of the NFA's edges: bytes in a class are read
+    /// by exactly the same edges.
+    pub fn byte_classes(&self) -> ByteClasses {
+        return ByteClasses::from_ranges(self.jumps.iter().flatten().map(|e| (e.lo, e.hi)));
+    }
+
+    /// States reachable from `state` by reading `c`.
+    pub fn next(&self, state: usize, c: u8) -> impl Iterator<Item = usize> + '_ {
+        return self.jumps[state].iter()
+            .filter(move |e| e.lo <= c && c <= e.hi)
+            .map(|e| e.to);
     }
 
     fn make_node(&mut self) -> usize {
         self.ncount += 1;
-        self.jumps.push([NULL; u8::MAX as usize]);
+        self.jumps.push(Vec::new());
         self.eps.push(Vec::new());
         self.accepts.push(0);
+        self.owners.push(0);
+        self.tags.push(Vec::new());
         return self.ncount - 1;
     }
 
@@ -187,30 +496,17 @@ impl NFA {
     pub fn print_dot(&self) {
         println!("digraph NFA {{");
         for state in 0..self.ncount {
-            let mut ind = 0;
-            while ind < u8::MAX {
-                let nbr = self.jumps[state][ind as usize];
-                if nbr == NULL { ind += 1; continue };
-
-                let start = ind;
-                while ind + 1 < u8::MAX &&
-                    self.jumps[state][(ind + 1) as usize] == nbr {
-                    ind += 1;
-                }
-
-                if start == ind {
+            for e in &self.jumps[state] {
+                if e.lo == e.hi {
                     println!("\t{} -> {} [label=\"{}\"];",
-                        state, nbr, (ind as char).escape_debug()
+                        state, e.to, (e.lo as char).escape_debug()
                     );
                 } else {
-                    println!("\t{} -> {} [label=\"{}\"];",
-                        state, nbr,
-                        format!("{}-{}", (start as char).escape_debug(),
-                            (ind as char).escape_debug()
-                        )
+                    println!("\t{} -> {} [label=\"{}-{}\"];",
+                        state, e.to, (e.lo as char).escape_debug(),
+                        (e.hi as char).escape_debug()
                     );
                 }
-                ind += 1;
             }
             for nbr in &self.eps[state] {
                 println!("\t{} -> {} [label=\"eps\"];",
@@ -222,24 +518,44 @@ impl NFA {
     }
 }
 
+// Whitespace rules may only be alternations of characters and classes.
+struct Whitespace {
+    ranges: Vec<(char, char)>,
+    props: Vec<Property>,
+    valid: bool
+}
+
+impl Visitor for Whitespace {
+    fn visit_binary(&mut self, node: &ast::BinaryExprNode) {
+        if node.op != lexer::Op::BAR { self.valid = false; }
+        visit::walk_binary(self, node);
+    }
+    fn visit_unary(&mut self, _node: &ast::UnaryExprNode) { self.valid = false; }
+    fn visit_class(&mut self, ranges: &[(char, char)]) {
+        self.ranges.extend_from_slice(ranges);
+    }
+    fn visit_property(&mut self, prop: Property) { self.props.push(prop); }
+    fn visit_char(&mut self, c: char) { self.ranges.push((c, c)); }
+}
+
 #[cfg(test)]
 mod tests {
     use std::{path::Path, fs::File, io::{BufReader, BufRead}};
-    use crate::{lexer::Lexer, parser::Parser};
+    use crate::{lexer::Lexer, parser::{Parser, parse_spec}};
 
     use super::*;
     impl NFA {
         fn accepts(&self, s: &str) -> bool {
             let mut states: Vec<usize> = self.eps_closure(vec![0; 1]);
-            for c in s.chars() {
+            for c in s.bytes() {
                 let mut has = vec![false; self.ncount];
                 let mut mv: Vec<usize> = Vec::new();
                 for s in &states {
-                    let nxt = self.jumps[*s][c as usize];
-                    if nxt != NULL && !has[nxt] { 
+                    for nxt in self.next(*s, c) {
+                        if has[nxt] { continue; }
                         has[nxt] = true;
-                        mv.push(nxt); 
-                    } 
+                        mv.push(nxt);
+                    }
                 }
                 states = self.eps_closure(mv);
             }
@@ -251,25 +567,6 @@ mod tests {
             }
             return false;
         }
-
-        fn eps_closure(&self, set: Vec<usize>) -> Vec<usize> {
-            let mut has = vec![false; self.ncount];
-            let mut closure: Vec<usize> = set;
-            let mut stack: Vec<usize> = Vec::new();
-            for s in &closure { 
-                stack.push(*s); 
-                has[*s] = true;
-            }
-            while let Some(s) = stack.pop() {
-                for nbr in &self.eps[s] {
-                    if has[*nbr] { continue; }
-                    has[*nbr] = true;
-                    closure.push(*nbr);
-                    stack.push(*nbr);
-                }
-            }
-            return closure;
-        }
     }
 
     #[test]
@@ -277,22 +574,91 @@ mod tests {
         let path = "tests/data/regex/input";
         let mut i = 0;
         while Path::new(&format!("{path}/match-{i}.txt")).exists() {
-            println!("{}", format!("{path}/match-{i}.txt"));
+            println!("{path}/match-{i}.txt");
             let lexer = Lexer::new(&format!("{path}/match-{i}.txt")).expect("Invalid Path");
             let mut parser = Parser::new(lexer).expect("File should be non-empty!");
-            let nfa = NFA::build_from_matches(&parser.parse().expect("Invalid parse"));
+            let nfa = NFA::build_from_matches(&parser.parse().expect("Invalid parse")).unwrap();
             // nfa.print_dot();
+            let eps_free = nfa.remove_eps();
+            assert!(eps_free.eps.iter().all(|e| e.is_empty()));
             for id in ["right", "wrong"] {
-                let file = File::open(&format!("{path}/{id}-words-{i}.txt"))
+                let file = File::open(format!("{path}/{id}-words-{i}.txt"))
                     .expect("Should be valid...");
                 let reader = BufReader::new(file);
-                for line in reader.lines() {
-                    if let Ok(word) = line {
-                        assert!(nfa.accepts(&word) == (id == "right"));
-                    }
+                for word in reader.lines().map_while(Result::ok) {
+                    assert!(nfa.accepts(&word) == (id == "right"));
+                    assert!(eps_free.accepts(&word) == (id == "right"));
                 }
             }
             i += 1;
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn sparse_class() {
+        let matches = parse_spec("\"[a-z0-9_]\" X;").unwrap();
+        let nfa = NFA::build_from_matches(&matches).unwrap();
+        assert!(nfa.ncount == 3);
+        assert!(nfa.jumps[1] == vec![
+            Edge { lo: b'0', hi: b'9', to: 2 },
+            Edge { lo: b'_', hi: b'_', to: 2 },
+            Edge { lo: b'a', hi: b'z', to: 2 },
+        ]);
+        assert!(nfa.next(1, b'q').eq([2]) && nfa.next(1, b'-').count() == 0);
+    }
+
+    #[test]
+    fn remove_eps() {
+        let matches = parse_spec("\"if\" IF; \"[a-z]+\" ID; \"(ab)*c*\" ABC; \" \" ;").unwrap();
+        let nfa = NFA::build_from_matches(&matches).unwrap().remove_eps();
+        assert!(nfa.ncount < NFA::build_from_matches(&matches).unwrap().ncount);
+        assert!(nfa.labels == ["IF", "ID", "ABC"]);
+        // The start state keeps the whitespace loop and accepts ABC's empty string.
+        assert!(nfa.next(0, b' ').eq([0]) && nfa.accepts[0] == 3);
+        let after = |word: &str| -> Vec<usize> {
+            let mut states = vec![0; 1];
+            for c in word.bytes() {
+                states = states.iter().flat_map(|s| nfa.next(*s, c)).collect();
+            }
+            return states.iter().map(|s| nfa.accepts[*s]).filter(|a| *a != 0).collect();
+        };
+        assert!(after("if").contains(&1) && after("if").contains(&2));
+        assert!(after("abcc").contains(&3) && !after("abca").contains(&3));
+    }
+
+    #[test]
+    fn properties() {
+        let matches = parse_spec("\"\\p{XID_Start}\\p{XID_Continue}*\" ID;").unwrap();
+        let nfa = NFA::build_from_matches(&matches).unwrap();
+        for word in ["x", "café", "λ_1", "变量", "Ωmega"] { assert!(nfa.accepts(word), "{word}"); }
+        for word in ["", "_x", "1a", "a-b", "a b"] { assert!(!nfa.accepts(word), "{word}"); }
+    }
+
+    #[test]
+    fn build_errors() {
+        let rule = |root: ast::Node, name: &str| ast::Match {
+            root, name: name.to_string(), priority: 0, span: Span { start: 0, end: 0 }
+        };
+        let binary = |op, left, right| ast::Node::BinaryExpr(ast::BinaryExprNode {
+            left: Box::new(left), right: Box::new(right), op
+        });
+        let mut matches = parse_spec("\"a\" A; \" \" ;").unwrap();
+        matches.push(rule(binary(lexer::Op::STAR, ast::Node::Char('a'), ast::Node::Char('b')), "X"));
+        let err = NFA::build_from_matches(&matches).err();
+        assert!(err == Some(BuildError::UnexpectedOp { rule: 2, op: lexer::Op::STAR }));
+
+        let class = ast::Node::Class(vec![('a', 'b')]);
+        matches[2] = rule(binary(lexer::Op::DASH, class, ast::Node::Char('z')), "X");
+        assert!(NFA::build_from_matches(&matches).err() == Some(BuildError::InvalidRange { rule: 2 }));
+
+        let ws = parse_spec("\"a*\" ;").unwrap().remove(0);
+        assert!(NFA::build_from_matches(&[ws]).err() == Some(BuildError::Whitespace { rule: 0 }));
+
+        let matches = parse_spec("\"a\" A; \"λ\" L; \"\\p{L}\" ;").unwrap();
+        let err = NFA::build_with_encoding(&matches, Encoding::Latin1).err().unwrap();
+        assert!(err == BuildError::OutOfRange { rule: 1, c: 'λ' } && err.rule() == 1);
+        // Properties are clipped to Latin-1 instead.
+        let matches = parse_spec("\"\\p{L}\" ;").unwrap();
+        assert!(NFA::build_with_encoding(&matches, Encoding::Latin1).is_ok());
+    }
+}
@@ -0,0 +1,290 @@
+use std::collections::{HashMap, VecDeque};
+use crate::ast::Match;
+use crate::dfa::{ByteClasses, DFA, ALPHABET};
+use crate::nfa::{BuildError, Encoding, NFA};
+
+// Language operations on DFAs. They read a DFA as the set of words it
+// accepts with any rule, and build DFAs with a single label that accept
+// a word or not.
+
+/// The DFA of rule `m` alone, with no other rule or whitespace around
+/// it, so `language(float)?.is_subset_of(&language(number)?)` asks
+/// whether every FLOAT is also a NUMBER whichever of them wins the tie.
+pub fn language(m: &Match, encoding: Encoding) -> Result<DFA, BuildError> {
+    let nfa = NFA::build_rule(m, encoding)?;
+    return Ok(DFA::compress(DFA::subset_construction(nfa)));
+}
+
+impl DFA {
+    /// The words this DFA tokenizes as `rule`, after priorities and
+    /// declaration order settled ties, or `None` if no rule has that name.
+    /// Whitespace before a token is not part of it, so none is accepted.
+    pub fn project(&self, rule: &str) -> Option<DFA> {
+        let id = self.labels.iter().position(|label| label == rule)? + 1;
+        let accepts = self.accepts.iter().map(|acc| (*acc == id) as usize).collect();
+        // Only whitespace leads back to the start state, so cutting the
+        // edges into it drops the leading whitespace.
+        let jumps = self.jumps.iter()
+            .map(|row| row.iter().map(|t| if *t == 0 { self.dead } else { *t }).collect())
+            .collect();
+        let dfa = DFA {
+            ncount:  self.ncount,
+            jumps,
+            classes: self.classes.clone(),
+            accepts,
+            labels:  vec![rule.to_string()],
+            dead:    self.dead
+        };
+        return Some(DFA::compress(dfa));
+    }
+
+    /// The words both DFAs accept.
+    pub fn intersection(&self, other: &DFA) -> DFA {
+        return DFA::product(self, other, |a, b| a && b, format!("({}) & ({})", self.name(), other.name()));
+    }
+
+    /// The words either DFA accepts.
+    pub fn union(&self, other: &DFA) -> DFA {
+        return DFA::product(self, other, |a, b| a || b, format!("({}) | ({})", self.name(), other.name()));
+    }
+
+    /// The words this DFA accepts and `other` does not.
+    pub fn difference(&self, other: &DFA) -> DFA {
+        return DFA::product(self, other, |a, b| a && !b, format!("({}) - ({})", self.name(), other.name()));
+    }
+
+    /// The byte strings this DFA does not accept. Under UTF-8 these
+    /// include strings that are not valid UTF-8.
+    pub fn complement(&self) -> DFA {
+        // The old dead state accepts now, so a fresh one takes its place.
+        let dead = self.ncount;
+        let k = self.classes.len();
+        let mut jumps = self.jumps.clone();
+        jumps.push(vec![dead; k]);
+        let mut accepts: Vec<usize> = self.accepts.iter().map(|acc| (*acc == 0) as usize).collect();
+        accepts.push(0);
+        let dfa = DFA {
+            ncount:  self.ncount + 1,
+            jumps,
+            classes: self.classes.clone(),
+            accepts,
+            labels:  vec![format!("!({})", self.name())],
+            dead
+        };
+        return DFA::compress(dfa);
+    }
+
+    /// A shortest word this DFA accepts, or `None` if it accepts nothing.
+    pub fn example(&self) -> Option<Vec<u8>> {
+        let reps = representatives(&self.classes);
+        // How each state was first reached, by breadth-first search.
+        let mut prev: Vec<Option<(usize, u8)>> = vec![None; self.ncount];
+        let mut seen = vec![false; self.ncount];
+        let mut queue = VecDeque::from([0]);
+        seen[0] = true;
+        while let Some(state) = queue.pop_front() {
+            if self.accepts[state] != 0 {
+                let mut word = Vec::new();
+                let mut at = state;
+                while let Some((from, b)) = prev[at] {
+                    word.push(b);
+                    at = from;
+                }
+                word.reverse();
+                return Some(word);
+            }
+            for (k, &to) in self.jumps[state].iter().enumerate() {
+                if !seen[to] {
+                    seen[to] = true;
+                    prev[to] = Some((state, reps[k]));
+                    queue.push_back(to);
+                }
+            }
+        }
+        return None;
+    }
+
+    /// `Ok` if this DFA accepts nothing, otherwise a shortest word it
+    /// accepts.
+    pub fn check_empty(&self) -> Result<(), Vec<u8>> {
+        return match self.example() {
+            Some(word) => Err(word),
+            None => Ok(())
+        };
+    }
+
+    /// `Ok` if `other` accepts every word this DFA accepts, otherwise a
+    /// shortest word it accepts that `other` does not.
+    pub fn is_subset_of(&self, other: &DFA) -> Result<(), Vec<u8>> {
+        return self.difference(other).check_empty();
+    }
+
+    /// `Ok` if both DFAs accept the same words, otherwise a shortest
+    /// word only one of them accepts.
+    pub fn equivalent(&self, other: &DFA) -> Result<(), Vec<u8>> {
+        let sym = DFA::product(self, other, |a, b| a != b, String::new());
+        return sym.check_empty();
+    }
+
+    // The reachable part of the product of `a` and `b`, over classes
+    // that refine both of theirs, accepting where `keep` says so.
+    // `keep(false, false)` must be false so the pair of dead states
+    // stays dead.
+    fn product(a: &DFA, b: &DFA, keep: impl Fn(bool, bool) -> bool, label: String) -> DFA {
+        debug_assert!(!keep(false, false));
+        let classes = ByteClasses::from_ranges(ranges(&a.classes).into_iter()
+            .chain(ranges(&b.classes)));
+        let firsts = firsts(&classes);
+        let mut pairs = vec![(0, 0)];
+        let mut ids = HashMap::from([((0, 0), 0)]);
+        let dead = *ids.entry((a.dead, b.dead)).or_insert_with(|| {
+            pairs.push((a.dead, b.dead));
+            return 1;
+        });
+        let mut jumps = Vec::new();
+        let mut accepts = Vec::new();
+        let mut i = 0;
+        while i < pairs.len() {
+            let (p, q) = pairs[i];
+            let mut row = Vec::with_capacity(classes.len());
+            for &c in &firsts {
+                let pair = (a.next(p, c), b.next(q, c));
+                let id = *ids.entry(pair).or_insert_with(|| {
+                    pairs.push(pair);
+                    return pairs.len() - 1;
+                });
+                row.push(id);
+            }
+            jumps.push(row);
+            accepts.push(keep(a.accepts[p] != 0, b.accepts[q] != 0) as usize);
+            i += 1;
+        }
+        let dfa = DFA {
+            ncount: pairs.len(),
+            jumps,
+            classes,
+            accepts,
+            labels: vec![label],
+            dead
+        };
+        return DFA::compress(dfa);
+    }
+
+    // The labels this DFA accepts with, as one name.
+    fn name(&self) -> String {
+        return self.labels.join(" | ");
+    }
+}
+
+// The bytes of each class, as runs from lowest to highest.
+fn ranges(classes: &ByteClasses) -> Vec<(u8, u8)> {
+    let map = classes.map();
+    let mut ranges = Vec::new();
+    let mut lo = 0;
+    for b in 1..=ALPHABET {
+        if b == ALPHABET || map[b] != map[lo] {
+            ranges.push((lo as u8, (b - 1) as u8));
+            lo = b;
+        }
+    }
+    return ranges;
+}
+
+// The lowest byte of each class.
+fn firsts(classes: &ByteClasses) -> Vec<u8> {
+    return ranges(classes).into_iter().map(|(lo, _)| lo).collect();
+}
+
+// A byte of each class to spell counterexamples with, readable where
+// the class allows it.
+fn representatives(classes: &ByteClasses) -> Vec<u8> {
+    let mut reps: Vec<Option<u8>> = vec![None; classes.len()];
+    let preferred = (0..=u8::MAX).filter(u8::is_ascii_alphanumeric)
+        .chain((0..=u8::MAX).filter(u8::is_ascii_graphic))
+        .chain([b' '])
+        .chain(0..=u8::MAX);
+    for b in preferred {
+        let rep = &mut reps[classes.get(b)];
+        if rep.is_none() { *rep = Some(b); }
+    }
+    return reps.into_iter().map(|rep| rep.expect("Every class has a byte")).collect();
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::parser::parse_spec;
+    use super::*;
+
+    const SPEC: &str = "\"if|while\" KEYWORD;
+        \"[a-z]+\" IDENT;
+        \"[0-9]+.[0-9]+\" FLOAT;
+        \"[0-9]+|[0-9]+.[0-9]+\" NUMBER;
+        \" \" ;";
+
+    fn rule(name: &str) -> DFA {
+        let matches = parse_spec(SPEC).unwrap();
+        let m = matches.iter().find(|m| m.name == name).unwrap();
+        return language(m, Encoding::Utf8).unwrap();
+    }
+
+    #[test]
+    fn products() {
+        let (ident, keyword) = (rule("IDENT"), rule("KEYWORD"));
+        let both = ident.intersection(&keyword);
+        assert!(both.run(b"if") != 0 && both.run(b"while") != 0 && both.run(b"x") == 0);
+        let either = ident.union(&rule("NUMBER"));
+        assert!(either.run(b"x") != 0 && either.run(b"1.5") != 0 && either.run(b"x1") == 0);
+        let idents = ident.difference(&keyword);
+        assert!(idents.run(b"iff") != 0 && idents.run(b"if") == 0);
+        assert_eq!(idents.example(), Some(b"a".to_vec()));
+        assert_eq!(keyword.difference(&ident).example(), None);
+        for dfa in [both, either, idents] {
+            assert!(dfa.accepts[dfa.dead] == 0 && dfa.jumps[dfa.dead].iter().all(|s| *s == dfa.dead));
+        }
+    }
+
+    #[test]
+    fn complement() {
+        let ident = rule("IDENT");
+        let not = ident.complement();
+        assert!(not.run(b"") != 0 && not.run(b"a1") != 0 && not.run([0xff]) != 0 && not.run(b"abc") == 0);
+        assert!(not.accepts[not.dead] == 0);
+        assert_eq!(not.complement().equivalent(&ident), Ok(()));
+        assert_eq!(ident.intersection(&not).check_empty(), Ok(()));
+        assert_eq!(not.example(), Some(Vec::new()));
+    }
+
+    #[test]
+    fn inclusion() {
+        let (float, number) = (rule("FLOAT"), rule("NUMBER"));
+        assert_eq!(float.is_subset_of(&number), Ok(()));
+        assert_eq!(number.is_subset_of(&float), Err(b"0".to_vec()));
+        assert_eq!(rule("KEYWORD").is_subset_of(&rule("IDENT")), Ok(()));
+        assert_eq!(float.equivalent(&number), Err(b"0".to_vec()));
+        assert_eq!(rule("IDENT").check_empty(), Err(b"a".to_vec()));
+    }
+
+    #[test]
+    fn projections() {
+        let matches = parse_spec(SPEC).unwrap();
+        let nfa = NFA::build_from_matches(&matches).unwrap();
+        let dfa = DFA::compress(DFA::subset_construction(nfa));
+        assert!(dfa.project("NOPE").is_none());
+        let ident = dfa.project("IDENT").unwrap();
+        // KEYWORD comes first, so it wins its words from IDENT.
+        assert_eq!(ident.intersection(&rule("KEYWORD")).check_empty(), Ok(()));
+        assert!(ident.run(b"iff") != 0 && ident.run(b" x") == 0 && ident.run(b"if") == 0);
+        assert_eq!(ident.equivalent(&rule("IDENT").difference(&rule("KEYWORD"))), Ok(()));
+        // FLOAT wins every word it shares with NUMBER.
+        let number = dfa.project("NUMBER").unwrap();
+        assert_eq!(number.intersection(&rule("FLOAT")).check_empty(), Ok(()));
+        assert_eq!(number.equivalent(&rule("NUMBER").difference(&rule("FLOAT"))), Ok(()));
+        assert_eq!(rule("NUMBER").is_subset_of(&number), Err(b"0.0".to_vec()));
+
+        // Whitespace of several bytes is dropped whole.
+        let matches = parse_spec("\"[a-z]+\" ID; \"[ \\xa0]\" ;").unwrap();
+        let dfa = DFA::compress(DFA::subset_construction(NFA::build_from_matches(&matches).unwrap()));
+        let id = dfa.project("ID").unwrap();
+        assert_eq!(id.equivalent(&language(&matches[0], Encoding::Utf8).unwrap()), Ok(()));
+    }
+}
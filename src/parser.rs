@@ -1,5 +1,6 @@
-use crate::lexer::{TokenGiver, Token, TokenErr, Group, Op};
-use crate::ast::{Node, BinaryExprNode, UnaryExprNode, Match};
+use std::fmt;
+use crate::lexer::{Lexer, TokenGiver, Token, TokenErr, Group, Op, Span};
+use crate::ast::{Node, BinaryExprNode, UnaryExprNode, CaptureNode, Match};
 use Token::*;
 use Group::*;
 use Op::*;
@@ -14,9 +15,32 @@ impl From<TokenErr> for ParseError {
         return ParseError::Token(err);
     }
 }
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return match self {
+            ParseError::Parse(msg) => write!(f, "{msg}"),
+            ParseError::Token(err) => write!(f, "{err}"),
+        };
+    }
+}
+impl std::error::Error for ParseError {}
+
+/// Parses a single regex in the spec dialect, written without the
+/// surrounding quotes, e.g. `[a-zA-Z][a-zA-Z0-9]*`.
+pub fn parse_regex(src: &str) -> Result<Node, ParseError> {
+    let mut parser = Parser::new(Lexer::from_source(src, true))?;
+    return parser.regex();
+}
+
+/// Parses the contents of a .tk specification.
+pub fn parse_spec(src: &str) -> Result<Vec<Match>, ParseError> {
+    let mut parser = Parser::new(Lexer::from(src))?;
+    return parser.parse();
+}
 
 pub struct Parser<T: TokenGiver> {
     cur: Token,
+    span: Span,
     lexer: T,
 }
 
@@ -24,13 +48,19 @@ impl<T: TokenGiver> Parser<T> {
     pub fn new(mut lexer: T) -> Result<Self, ParseError> {
         return Ok(Parser { 
             cur: lexer.next()?, 
+            span: lexer.span(),
             lexer,
         });
     }
 
+    pub fn lexer(&self) -> &T {
+        return &self.lexer;
+    }
+
     fn advance(&mut self) -> Result<Token, ParseError> {
         let temp = self.cur;
         self.cur = self.lexer.next()?;
+        self.span = self.lexer.span();
         return Ok(temp);
     }
 
@@ -49,11 +79,12 @@ impl<T: TokenGiver> Parser<T> {
     pub fn parse(&mut self) -> Result<Vec<Match>, ParseError> {
         let mut matches = Vec::new();
         while let GROUP(DBQ) = self.cur {
+            let start = self.span.start;
             self.consume(GROUP(DBQ), "Parse")?;
             let root = self.expr()?;
             self.consume(GROUP(DBQ), "Parse")?;
-            let name = self.name()?; 
-            matches.push(Match { root, name: name });
+            let (name, priority, end) = self.name()?;
+            matches.push(Match { root, name, priority, span: Span { start, end } });
         }
         if self.cur != EOF { 
             return Err(ParseError::Parse(
@@ -63,6 +94,16 @@ impl<T: TokenGiver> Parser<T> {
         return Ok(matches);
     }
 
+    pub fn regex(&mut self) -> Result<Node, ParseError> {
+        let root = self.expr()?;
+        if self.cur != EOF {
+            return Err(ParseError::Parse(
+                format!("Regex: Expected EOF but got {:?}", self.cur)
+            ));
+        }
+        return Ok(root);
+    }
+
     fn expr(&mut self) -> Result<Node, ParseError> {
         let mut root = self.term()?;
         while let OP(BAR) = self.cur {
@@ -80,7 +121,7 @@ impl<T: TokenGiver> Parser<T> {
 
     fn term(&mut self) -> Result<Node, ParseError> {
         let mut root = self.factor()?;
-        while let CHAR(_) | GROUP(LPR) | GROUP(LBR) = self.cur {
+        while let CHAR(_) | PROP(_) | GROUP(LPR) | GROUP(LBR) = self.cur {
             let node= self.factor()?;
             let new_root = BinaryExprNode {
                 op: AND,
@@ -109,39 +150,63 @@ impl<T: TokenGiver> Parser<T> {
     fn atom(&mut self) -> Result<Node, ParseError> {
         match self.advance()? {
             GROUP(LPR) => { 
+                if self.cur == CHAR('?') && self.lexer.peek()? == CHAR('<') {
+                    return self.capture();
+                }
                 let node = self.expr()?;
                 self.consume(GROUP(RPR), "Atom")?;
                 return Ok(node);
             },
             CHAR(c) => return Ok(Node::Char(c)),
-            GROUP(LBR) => return Ok(self.bracketed()?),
+            PROP(p) => return Ok(Node::Property(p)),
+            GROUP(LBR) => return self.bracketed(),
             token => Err(ParseError::Parse(
                 format!("Atom: Expected CHAR, [, (, but found {:?}", token)
             ))
         }
     }
 
+    // (?<name>expr), after the opening parenthesis.
+    fn capture(&mut self) -> Result<Node, ParseError> {
+        self.consume(CHAR('?'), "Capture")?;
+        self.consume(CHAR('<'), "Capture")?;
+        let mut name = String::new();
+        while let CHAR(c) = self.cur {
+            if c == '>' { break; }
+            if !(c.is_alphanumeric() || c == '_') || (name.is_empty() && c.is_ascii_digit()) {
+                return Err(ParseError::Parse(format!("Capture: Invalid group name character {:?}", c)));
+            }
+            name.push(c);
+            self.advance()?;
+        }
+        if name.is_empty() {
+            return Err(ParseError::Parse("Capture: Expected a group name".to_string()));
+        }
+        self.consume(CHAR('>'), "Capture")?;
+        let child = self.expr()?;
+        self.consume(GROUP(RPR), "Capture")?;
+        return Ok(Node::Capture(CaptureNode { name, child: Box::new(child) }));
+    }
+
+    // Properties in brackets are alternatives to the class, e.g.
+    // [_\p{L}] => [_]|\p{L}.
     fn bracketed(&mut self) -> Result<Node, ParseError> {
-        let mut root: Option<Node> = None;
+        let mut ranges: Vec<(char, char)> = Vec::new();
+        let mut props: Vec<Node> = Vec::new();
         loop { match self.cur {
-            CHAR(_) => {
-                let res = match self.lexer.peek()? {
-                    OP(DASH) => self.dash()?,
-                    CHAR(_) | GROUP(RBR) => self.atom()?,
-                    t => return Err(ParseError::Parse(format!(
-                        "Expected Char or Dash got {:?}", t)
-                    )),
-                };
-                root = match root {
-                    None => Some(res),
-                    Some(node) => Some(Node::BinaryExpr(
-                        BinaryExprNode { 
-                            left: Box::new(node), 
-                            right: Box::new(res), 
-                            op: BAR 
-                        }
-                    ))
-                }
+            PROP(p) => {
+                self.advance()?;
+                props.push(Node::Property(p));
+            },
+            CHAR(c) => match self.lexer.peek()? {
+                OP(DASH) => ranges.push(self.dash()?),
+                CHAR(_) | PROP(_) | GROUP(RBR) => {
+                    self.advance()?;
+                    ranges.push((c, c));
+                },
+                t => return Err(ParseError::Parse(format!(
+                    "Expected Char or Dash got {:?}", t)
+                )),
             },
             GROUP(RBR) => break,
             t => return Err(ParseError::Parse(format!(
@@ -149,62 +214,79 @@ impl<T: TokenGiver> Parser<T> {
             )))
         }}
         self.consume(GROUP(RBR), "Dashes")?;
-        match root {
-            None => return Err(ParseError::Parse(
-                format!("Invalid Bracketed Expression")
-            )),
-            Some(node) => return Ok(node)
+        if !ranges.is_empty() { props.insert(0, Node::Class(ranges)); }
+        let mut nodes = props.into_iter();
+        let mut root = nodes.next().ok_or(ParseError::Parse(
+            "Invalid Bracketed Expression".to_string()
+        ))?;
+        for node in nodes {
+            root = Node::BinaryExpr(BinaryExprNode {
+                op: BAR, left: Box::new(root), right: Box::new(node)
+            });
         }
+        return Ok(root);
     }
 
-    fn dash(&mut self) -> Result<Node, ParseError> {
-        let root: Node;
+    fn dash(&mut self) -> Result<(char, char), ParseError> {
         let c = self.advance()?.char();
-        if c.is_digit(10) {
-            self.consume(OP(DASH), "Dash")?;
-            let d = self.advance()?.char();
-            if d.is_digit(10) { 
-                root = Node::BinaryExpr(BinaryExprNode {
-                    op: DASH,
-                    left: Box::new(Node::Char(c)),
-                    right: Box::new(Node::Char(d))
-                });
-            } else {
-                return Err(ParseError::Parse(
-                    format!("Dash: Expected Num-Num but got Num-{}", d)
-                ));
-            }
-        } else if c.is_alphabetic() {
-            self.consume(OP(DASH), "Dash")?;
-            let d = self.advance()?.char();
-            if d.is_alphabetic() {
-                root = Node::BinaryExpr(BinaryExprNode {
-                    op: DASH,
-                    left: Box::new(Node::Char(c)),
-                    right: Box::new(Node::Char(d))
-                });
-            } 
-            else {
-                return Err(ParseError::Parse(
-                    format!("Dash: Expected Alpha-Alpha but got Alpha-{}", d)
-                ));
-            }
-        } else {
+        if !c.is_ascii_digit() && !c.is_alphabetic() {
             return Err(ParseError::Parse(
                 format!("Dash: Expected Alphanumeric but got {}", c)
             ));
         }
-        return Ok(root);
+        self.consume(OP(DASH), "Dash")?;
+        let d = match self.advance()? {
+            CHAR(d) => d,
+            t => return Err(ParseError::Parse(
+                format!("Dash: Expected Char after {}- but got {:?}", c, t)
+            ))
+        };
+        if c.is_ascii_digit() && !d.is_ascii_digit() {
+            return Err(ParseError::Parse(
+                format!("Dash: Expected Num-Num but got Num-{}", d)
+            ));
+        }
+        if c.is_alphabetic() && !d.is_alphabetic() {
+            return Err(ParseError::Parse(
+                format!("Dash: Expected Alpha-Alpha but got Alpha-{}", d)
+            ));
+        }
+        if d < c {
+            return Err(ParseError::Parse(
+                format!("Dash: Range {}-{} is reversed", c, d)
+            ));
+        }
+        return Ok((c, d));
     }
 
-    fn name(&mut self) -> Result<String, ParseError> {
+    // Returns the name, the priority given by an optional `@N` after
+    // it, and the offset just past its ';'.
+    fn name(&mut self) -> Result<(String, i32, usize), ParseError> {
         let mut name: String = String::new();
         while let CHAR(c) = self.cur {
+            if c == '@' { break; }
             name.push(c);
             self.advance()?;
         }
+        let mut priority = 0;
+        if self.cur == CHAR('@') {
+            self.advance()?;
+            let mut digits = String::new();
+            if self.cur == OP(DASH) {
+                digits.push('-');
+                self.advance()?;
+            }
+            while let CHAR(c) = self.cur {
+                digits.push(c);
+                self.advance()?;
+            }
+            priority = digits.parse().map_err(|_| ParseError::Parse(
+                format!("Name: Expected a priority but got {:?}", digits)
+            ))?;
+        }
+        let end = self.span.end;
         self.consume(SEMI, "Name")?;
-        return Ok(name);
+        return Ok((name, priority, end));
     }
 }
 
@@ -212,7 +294,6 @@ impl<T: TokenGiver> Parser<T> {
 mod tests {
     use super::*;
     use std::{fs, path::Path};
-    use crate::lexer::Lexer;
 
     #[test]
     fn ast() {
@@ -220,7 +301,7 @@ mod tests {
         while Path::new(&format!("tests/data/parser/input/AST-{i}.txt")).exists() {
             let inpath = &format!("tests/data/parser/input/AST-{i}.txt");
             let outpath = &format!("tests/data/parser/output/AST-{i}.txt");
-            let tr = Lexer::new(&inpath).expect("File Doesn't Exist");
+            let tr = Lexer::new(inpath).expect("File Doesn't Exist");
             let mut parser = Parser::new(tr).expect("Invalid Token Stream");
             let matches = parser.parse().expect("Expression should be valid.");
             for m in matches { 
@@ -230,4 +311,56 @@ mod tests {
             i += 1;
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn strings() {
+        let root = parse_regex("[a-zA-Z][a-zA-Z0-9]*").expect("Valid regex");
+        let ans = parse_spec("\"[a-zA-Z][a-zA-Z0-9]*\" IDENT;")
+            .expect("Valid spec");
+        assert!(ans.len() == 1 && ans[0].name == "IDENT");
+        assert!(root.to_string() == ans[0].root.to_string());
+
+        assert!(parse_regex("a b\\+").is_ok());
+        assert!(parse_regex("(ab").is_err());
+        assert!(parse_regex("ab)").is_err());
+        assert!(parse_regex("a\\").is_err());
+        assert!(parse_spec("\"ab\" AB").is_err());
+        for src in ["[a-]", "[a-", "[z-a]", "[9-0]", "[a-9]", "[1-z]"] {
+            assert!(parse_regex(src).is_err(), "{src}");
+        }
+        assert!(parse_spec("\"[a-]\" X;").is_err());
+        assert!(parse_regex("[a-a0-9]").is_ok());
+
+        let ans = parse_spec("\"a\" A @2; \"b\" B@-1; \"c\" C;").expect("Valid spec");
+        let priorities: Vec<i32> = ans.iter().map(|m| m.priority).collect();
+        assert!(priorities == [2, -1, 0] && ans[1].name == "B");
+        for src in ["\"a\" A @;", "\"a\" A @x;", "\"a\" A @1 B;", "\"a\" A @-;"] {
+            assert!(parse_spec(src).is_err(), "{src}");
+        }
+
+        let root = parse_regex("\\p{XID_Start}[_\\P{Nd}]").expect("Valid regex");
+        assert!(crate::printer::regex(&root) == "\\p{XID_Start}([_]|\\P{Nd})");
+        assert!(parse_regex("\\p{Nope}").is_err());
+        assert!(parse_regex("\\p{L").is_err());
+
+        let root = parse_regex("0x(?<digits>[0-9]+)(?<rest>a(?<b>b))*").expect("Valid regex");
+        assert!(root.groups() == ["digits", "rest", "b"]);
+        for src in ["(?<>a)", "(?<1x>a)", "(?<a b>c)", "(?<a>b", "(?<a"] {
+            assert!(parse_regex(src).is_err(), "{src}");
+        }
+        // Without the <, the ? is a literal as anywhere else.
+        assert!(parse_regex("(?a)").unwrap().groups().is_empty());
+    }
+
+    #[test]
+    fn question_mark() {
+        // A bare ? is a literal, as it always was, and so is \?, which
+        // the printer writes.
+        let cat = |left: char, right: char| Node::BinaryExpr(BinaryExprNode {
+            op: AND, left: Box::new(Node::Char(left)), right: Box::new(Node::Char(right))
+        });
+        assert!(parse_regex("b?").unwrap() == cat('b', '?'));
+        assert!(parse_regex("b\\?").unwrap() == cat('b', '?'));
+        assert!(crate::printer::regex(&parse_regex("a?").unwrap()) == "a\\?");
+    }
+}
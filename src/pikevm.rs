@@ -0,0 +1,263 @@
+use std::{error::Error, fmt};
+use crate::ast::{self, Match};
+use crate::lexer::Span;
+use crate::nfa::{NFA, Encoding, BuildError};
+use crate::parser;
+
+/// Tokenizes input by simulating the rules' NFA directly, so a spec
+/// loaded at runtime can be used without generating code or building
+/// a DFA. All threads advance in lockstep over the input, which keeps
+/// matching linear in its length.
+pub struct PikeVM {
+    nfa: NFA,
+    // The states of whitespace rules, which are those that lead back to
+    // the start.
+    whitespace: Vec<bool>,
+    // The tagged NFA of each rule with capture groups.
+    rules: Vec<Option<NFA>>
+}
+
+/// A token matched by a `PikeVM`. The span holds byte offsets, and
+/// `rule` indexes the named rules in priority order.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Token<'a> {
+    pub name: &'a str,
+    pub rule: usize,
+    pub span: Span
+}
+
+/// No rule matches the input at byte offset `pos`.
+#[derive(Debug, PartialEq, Eq)]
+pub struct LexError {
+    pub pos: usize
+}
+impl fmt::Display for LexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        return write!(f, "Failed to lex from byte {}", self.pos);
+    }
+}
+impl std::error::Error for LexError {}
+
+impl PikeVM {
+    pub fn new(matches: &[Match]) -> Result<Self, BuildError> {
+        let nfa = NFA::build_from_matches(matches)?.remove_eps();
+        let mut rules = Vec::new();
+        for m in ast::priority_order(matches).into_iter().map(|i| &matches[i]) {
+            if m.name.is_empty() { continue; }
+            if m.root.groups().is_empty() {
+                rules.push(None);
+            } else {
+                rules.push(Some(NFA::build_rule(m, Encoding::Utf8)?));
+            }
+        }
+        let mut whitespace = vec![false; nfa.ncount];
+        whitespace[0] = true;
+        let mut changed = true;
+        while changed {
+            changed = false;
+            for s in 0..nfa.ncount {
+                if !whitespace[s] && nfa.jumps[s].iter().any(|e| whitespace[e.to]) {
+                    whitespace[s] = true;
+                    changed = true;
+                }
+            }
+        }
+        return Ok(PikeVM { nfa, whitespace, rules });
+    }
+
+    pub fn from_spec(src: &str) -> Result<Self, Box<dyn Error>> {
+        return Ok(PikeVM::new(&parser::parse_spec(src)?)?);
+    }
+
+    /// The tokens of `input`, skipping whitespace rules between them.
+    /// Each token is the longest prefix of the rest of the input matched
+    /// by any rule; of the rules matching it, the one with the highest
+    /// priority wins, then the one declared first.
+    pub fn tokens<'a>(&'a self, input: &'a [u8]) -> Tokens<'a> {
+        return Tokens { vm: self, input, pos: 0, failed: false };
+    }
+
+    /// The capture groups of `token`, which was lexed from `input`, in
+    /// the order they open. A group that took no part in the match has
+    /// no span.
+    pub fn captures(&self, input: &[u8], token: &Token) -> Vec<(&str, Option<Span>)> {
+        let Some(Some(rule)) = self.rules.get(token.rule) else { return Vec::new() };
+        let Span { start, end } = token.span;
+        let spans = rule.captures(&input[start..end])
+            .expect("The token matched its rule");
+        return rule.groups.iter()
+            .zip(spans)
+            .map(|(name, span)| (name.as_str(), span.map(|s| Span {
+                start: start + s.start, end: start + s.end
+            })))
+            .collect();
+    }
+
+    /// The longest match starting at `start`, as its end and rule.
+    pub fn longest(&self, input: &[u8], start: usize) -> Option<(usize, usize)> {
+        let nfa = &self.nfa;
+        let mut best = None;
+        let mut threads: Vec<usize> = vec![0; 1];
+        let mut next: Vec<usize> = Vec::new();
+        // added[s] == pos + 1 once s has a thread for input[..pos].
+        let mut added = vec![0; nfa.ncount];
+        let mut pos = start;
+        loop {
+            let accept = threads.iter()
+                .map(|s| nfa.accepts[*s])
+                .filter(|acc| *acc != 0)
+                .min();
+            if let Some(acc) = accept { best = Some((pos, acc)); }
+            if pos == input.len() || threads.is_empty() { break; }
+            for s in &threads {
+                for t in nfa.next(*s, input[pos]) {
+                    if added[t] == pos + 1 { continue; }
+                    added[t] = pos + 1;
+                    next.push(t);
+                }
+            }
+            std::mem::swap(&mut threads, &mut next);
+            next.clear();
+            pos += 1;
+        }
+        return best;
+    }
+
+    // The end of the whitespace from `pos`. Whitespace rules are loops
+    // on the start state, so only whole loops are skipped, whatever
+    // their length in bytes.
+    pub(crate) fn skip(&self, input: &[u8], pos: usize) -> usize {
+        let mut end = pos;
+        let mut states: Vec<usize> = vec![0];
+        for (i, c) in input.iter().enumerate().skip(pos) {
+            let mut next: Vec<usize> = states.iter()
+                .flat_map(|s| self.nfa.next(*s, *c))
+                .filter(|t| self.whitespace[*t])
+                .collect();
+            next.sort_unstable();
+            next.dedup();
+            if next.is_empty() { break; }
+            if next.contains(&0) { end = i + 1; }
+            states = next;
+        }
+        return end;
+    }
+
+    // The eps-free NFA of all the rules.
+    pub(crate) fn nfa(&self) -> &NFA {
+        return &self.nfa;
+    }
+
+    // The name of the rule with accept id `rule + 1`.
+    pub(crate) fn label(&self, rule: usize) -> &str {
+        return &self.nfa.labels[rule];
+    }
+}
+
+pub struct Tokens<'a> {
+    vm: &'a PikeVM,
+    input: &'a [u8],
+    pos: usize,
+    failed: bool
+}
+
+impl<'a> Iterator for Tokens<'a> {
+    type Item = Result<Token<'a>, LexError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.failed { return None; }
+        self.pos = self.vm.skip(self.input, self.pos);
+        if self.pos == self.input.len() { return None; }
+        let start = self.pos;
+        match self.vm.longest(self.input, start) {
+            Some((end, acc)) if end > start => {
+                self.pos = end;
+                let name = self.vm.label(acc - 1);
+                return Some(Ok(Token { name, rule: acc - 1, span: Span { start, end } }));
+            },
+            _ => {
+                self.failed = true;
+                return Some(Err(LexError { pos: start }));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{fs, path::Path};
+    use super::*;
+
+    // Prints tokens the way the tester prints generated lexer tokens.
+    fn show(vm: &PikeVM, input: &[u8]) -> String {
+        let mut out = String::new();
+        for tk in vm.tokens(input) {
+            let tk = tk.expect("Input should lex");
+            let text = String::from_utf8_lossy(&input[tk.span.start..tk.span.end]);
+            let groups: Vec<String> = vm.captures(input, &tk).iter()
+                .map(|(name, span)| format!("Group {{ name: {:?}, span: {:?} }}", name,
+                    span.map(|s| (s.start - tk.span.start, s.end - tk.span.start))
+                ))
+                .collect();
+            if groups.is_empty() {
+                out.push_str(&format!("{}({:?}), ", tk.name, text));
+            } else {
+                out.push_str(&format!("{}({:?}, [{}]), ", tk.name, text, groups.join(", ")));
+            }
+        }
+        out.push_str("EOF");
+        return out;
+    }
+
+    #[test]
+    fn tester_data() {
+        let path = "tests/tester/data";
+        let mut i = 0;
+        while Path::new(&format!("{path}/gen-{i}.tk")).exists() {
+            let src = fs::read_to_string(format!("{path}/gen-{i}.tk")).unwrap();
+            let vm = PikeVM::from_spec(&src).expect("Valid spec");
+            let input = fs::read(format!("{path}/in-{i}.txt")).unwrap();
+            let ans = fs::read_to_string(format!("{path}/out-{i}.txt")).unwrap();
+            assert!(show(&vm, &input) == ans, "gen-{i}: {}", show(&vm, &input));
+            i += 1;
+        }
+        let mut i = 0;
+        while Path::new(&format!("{path}/ws-{i}.tk")).exists() {
+            let src = fs::read_to_string(format!("{path}/ws-{i}.tk")).unwrap();
+            let vm = PikeVM::from_spec(&src).expect("Valid spec");
+            let input = fs::read(format!("{path}/ws-in-{i}.txt")).unwrap();
+            let ans = fs::read_to_string(format!("{path}/ws-out-{i}.txt")).unwrap();
+            assert!(show(&vm, &input) == ans, "ws-{i}: {}", show(&vm, &input));
+            i += 1;
+        }
+    }
+
+    #[test]
+    fn priority_and_errors() {
+        let vm = PikeVM::from_spec("\"if\" IF; \"[a-z]+\" ID; \"=|==\" EQ; \" \" ;").unwrap();
+        let tokens: Vec<Token> = vm.tokens(b"if iff == x").map(Result::unwrap).collect();
+        let names: Vec<&str> = tokens.iter().map(|t| t.name).collect();
+        assert!(names == ["IF", "ID", "EQ", "ID"]);
+        assert!(tokens[2].span == Span { start: 7, end: 9 });
+
+        let mut tokens = vm.tokens(b"a ? b");
+        assert!(tokens.next().unwrap().is_ok());
+        assert!(tokens.next() == Some(Err(LexError { pos: 2 })));
+        assert!(tokens.next().is_none());
+        assert!(vm.tokens(b"   ").next().is_none());
+    }
+
+    #[test]
+    fn captures() {
+        let spec = "\"(?<int>[0-9]+)(.(?<frac>[0-9]+))*\" NUM; \"0x(?<digits>[0-9a-f]+)\" HEX; \" \" ;";
+        let vm = PikeVM::from_spec(spec).unwrap();
+        let input = b"1.25 7 0xff";
+        let groups: Vec<Vec<(&str, Option<Span>)>> = vm.tokens(input)
+            .map(|tk| vm.captures(input, &tk.unwrap()))
+            .collect();
+        let span = |start, end| Some(Span { start, end });
+        assert!(groups[0] == [("int", span(0, 1)), ("frac", span(2, 4))]);
+        assert!(groups[1] == [("int", span(5, 6)), ("frac", None)]);
+        assert!(groups[2] == [("digits", span(9, 11))]);
+    }
+}
@@ -0,0 +1,206 @@
+use crate::ast::{Node, Match};
+use crate::lexer::{Lexer, Op, Comment, Span};
+use crate::parser::{Parser, ParseError};
+
+/// Prints `node` in the spec dialect, without the surrounding quotes.
+/// Parsing the result gives back an identical tree, except that a DASH
+/// node comes back as the equivalent one-range Class.
+pub fn regex(node: &Node) -> String {
+    let mut out = String::new();
+    write_node(node, &mut out);
+    return out;
+}
+
+/// Reprints a .tk specification in canonical form: escapes normalised,
+/// rule names aligned, comments and paragraph breaks kept in place.
+pub fn format_spec(src: &str) -> Result<String, ParseError> {
+    let mut parser = Parser::new(Lexer::from(src))?;
+    let matches = parser.parse()?;
+    return Ok(format_matches(&matches, parser.lexer().comments(), src));
+}
+
+enum Item<'a> {
+    Rule(&'a Match, String),
+    Comment(&'a Comment)
+}
+
+/// Prints `matches` and `comments` in source order. `src` is only used
+/// to recover line numbers from spans, and may be empty.
+pub fn format_matches(matches: &[Match], comments: &[Comment], src: &str) -> String {
+    let newlines: Vec<usize> = src.chars()
+        .enumerate()
+        .filter(|(_, c)| *c == '\n')
+        .map(|(i, _)| i)
+        .collect();
+    let line = |offset: usize| newlines.partition_point(|nl| *nl < offset);
+
+    let mut items: Vec<(Span, Item)> = Vec::new();
+    for m in matches {
+        items.push((m.span, Item::Rule(m, format!("\"{}\"", regex(&m.root)))));
+    }
+    for c in comments {
+        items.push((c.span, Item::Comment(c)));
+    }
+    items.sort_by_key(|(span, _)| span.start);
+    let width = items.iter()
+        .filter_map(|(_, item)| match item {
+            Item::Rule(_, quoted) => Some(quoted.chars().count()),
+            Item::Comment(_) => None
+        })
+        .max()
+        .unwrap_or(0) + 2;
+
+    let mut out = String::new();
+    let mut last: Option<(usize, bool)> = None;
+    for (span, item) in &items {
+        let (start, end) = (line(span.start), line(span.end));
+        if let Item::Comment(c) = item {
+            if last == Some((start, true)) {
+                out.pop();
+                out.push_str(&format!("  #{}\n", c.text.trim_end()));
+                last = Some((end, false));
+                continue;
+            }
+        }
+        if let Some((prev, _)) = last {
+            if start > prev + 1 { out.push('\n'); }
+        }
+        match item {
+            Item::Rule(m, quoted) => {
+                let priority = match m.priority {
+                    0 => String::new(),
+                    p => format!(" @{p}")
+                };
+                out.push_str(&format!("{:<width$}{}{};\n", quoted, m.name, priority));
+            },
+            Item::Comment(c) => {
+                out.push_str(&format!("#{}\n", c.text.trim_end()));
+            }
+        }
+        last = Some((end, matches!(item, Item::Rule(..))));
+    }
+    return out;
+}
+
+fn write_node(node: &Node, out: &mut String) {
+    match node {
+        Node::Char(c) => out.push_str(&escape(*c)),
+        Node::Class(ranges) => write_class(ranges, out),
+        Node::Property(p) => {
+            let esc = if p.negated { 'P' } else { 'p' };
+            out.push_str(&format!("\\{esc}{{{}}}", p.name()));
+        },
+        Node::BinaryExpr(n) => match n.op {
+            Op::BAR => {
+                write_node(&n.left, out);
+                out.push('|');
+                write_group(&n.right, is_op(&n.right, &[Op::BAR]), out);
+            },
+            Op::DASH => write_class(&[(n.left.char(), n.right.char())], out),
+            _ => {
+                write_group(&n.left, is_op(&n.left, &[Op::BAR]), out);
+                write_group(&n.right, is_op(&n.right, &[Op::BAR, Op::AND]), out);
+            }
+        },
+        Node::Capture(n) => {
+            out.push_str(&format!("(?<{}>", n.name));
+            write_node(&n.child, out);
+            out.push(')');
+        },
+        Node::UnaryExpr(n) => {
+            let atom = matches!(*n.child, Node::Char(_) | Node::Class(_) | Node::Property(_) | Node::Capture(_))
+                || is_op(&n.child, &[Op::DASH]);
+            write_group(&n.child, !atom, out);
+            out.push_str(match n.op {
+                Op::STAR => "*",
+                Op::PLUS => "+",
+                _        => "?"
+            });
+        }
+    }
+}
+
+fn write_group(node: &Node, parens: bool, out: &mut String) {
+    if parens { out.push('('); }
+    write_node(node, out);
+    if parens { out.push(')'); }
+}
+
+fn write_class(ranges: &[(char, char)], out: &mut String) {
+    out.push('[');
+    for (lo, hi) in ranges {
+        let dash = (lo.is_ascii_digit() && hi.is_ascii_digit())
+            || (lo.is_alphabetic() && hi.is_alphabetic());
+        if lo == hi {
+            out.push_str(&escape(*lo));
+        } else if dash {
+            out.push_str(&format!("{}-{}", lo, hi));
+        } else {
+            for c in *lo..=*hi { out.push_str(&escape(c)); }
+        }
+    }
+    out.push(']');
+}
+
+fn is_op(node: &Node, ops: &[Op]) -> bool {
+    if let Node::BinaryExpr(n) = node { return ops.contains(&n.op); }
+    return false;
+}
+
+fn escape(c: char) -> String {
+    match c {
+        '\n' => return "\\n".to_string(),
+        '\t' => return "\\t".to_string(),
+        '\r' => return "\\r".to_string(),
+        '\\' | ']' | '[' | ')' | '(' | '{' | '}' | '-' |
+        '*' | ';' | '+' | '"' | '?' | '|' | '#' => return format!("\\{c}"),
+        c if c.is_control() => return format!("\\x{:02X}", c as u32),
+        _ => return c.to_string()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+    use crate::parser::parse_spec;
+
+    fn specs() -> Vec<String> {
+        let mut paths = vec!["example.tk".to_string()];
+        for dir in ["tests/tester/data", "tests/data/regex/input", "tests/data/lexer/input"] {
+            for entry in fs::read_dir(dir).expect("Directory exists") {
+                let path = entry.unwrap().path();
+                let name = path.file_name().unwrap().to_string_lossy().to_string();
+                if name.starts_with("gen-") || name.starts_with("match-") ||
+                    dir.ends_with("lexer/input") {
+                    paths.push(path.to_string_lossy().to_string());
+                }
+            }
+        }
+        return paths;
+    }
+
+    #[test]
+    fn round_trip() {
+        for path in specs() {
+            let src = fs::read_to_string(&path).unwrap();
+            let before = parse_spec(&src)
+                .unwrap_or_else(|e| panic!("{path}: {e}"));
+            let out = format_spec(&src).unwrap();
+            let after = parse_spec(&out).expect("Formatted spec should be valid.");
+            assert!(before.len() == after.len(), "{path}");
+            for (b, a) in before.iter().zip(&after) {
+                assert!(b.name == a.name && b.root == a.root && b.priority == a.priority, "{path}");
+            }
+            assert!(format_spec(&out).unwrap() == out, "{path}");
+            assert!(src.matches('#').count() == out.matches('#').count(), "{path}");
+        }
+    }
+
+    #[test]
+    fn layout() {
+        let src = "# Keywords\n\"if\" IF;   # trailing\n\n\n\"[a-z]+\"  IDENT;\n\"[ \\t]\" ;";
+        let ans = "# Keywords\n\"if\"      IF;  # trailing\n\n\"[a-z]+\"  IDENT;\n\"[ \\t]\"   ;\n";
+        assert!(format_spec(src).unwrap() == ans);
+    }
+}
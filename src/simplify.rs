@@ -0,0 +1,234 @@
+use std::mem;
+use crate::ast::{Node, Match, BinaryExprNode, UnaryExprNode};
+use crate::lexer::Op;
+use crate::visit::{VisitorMut, walk_node_mut};
+
+/// Algebraic clean-up run before the AST is lowered to an NFA. Every
+/// rewrite preserves the language of the rule:
+///  - characters and classes in an alternation merge into one class,
+///  - nested quantifiers collapse, e.g. (x*)* => x*, (x+)? => x*,
+///  - repeated or subsumed alternatives are dropped, e.g. x|x* => x*,
+///  - common prefixes are factored, e.g. ab|ac => a[bc], ab|a => ab?.
+///
+/// Alternations holding capture groups are left as they are, since
+/// merging their alternatives would drop or move a group.
+pub struct Simplify;
+
+impl VisitorMut for Simplify {
+    fn visit_node(&mut self, node: &mut Node) {
+        walk_node_mut(self, node);
+        let owned = mem::replace(node, Node::Class(Vec::new()));
+        *node = rewrite(owned);
+    }
+
+    fn visit_class(&mut self, ranges: &mut Vec<(char, char)>) {
+        *ranges = normalize(mem::take(ranges));
+    }
+}
+
+pub fn simplify(mut node: Node) -> Node {
+    Simplify.visit_node(&mut node);
+    return node;
+}
+
+pub fn simplify_matches(matches: &mut [Match]) {
+    for m in matches { Simplify.visit_match(m); }
+}
+
+// Children are already simplified.
+fn rewrite(node: Node) -> Node {
+    match node {
+        Node::UnaryExpr(n) => return quantify(n.op, *n.child),
+        Node::BinaryExpr(n) if n.op == Op::BAR
+            && n.left.groups().is_empty() && n.right.groups().is_empty() => {
+            let mut alts = Vec::new();
+            flatten(Node::BinaryExpr(n), Op::BAR, &mut alts);
+            return alternation(alts);
+        },
+        _ => return node
+    }
+}
+
+fn quantify(op: Op, child: Node) -> Node {
+    if let Node::UnaryExpr(inner) = child {
+        let op = if op == inner.op { op } else { Op::STAR };
+        return quantify(op, *inner.child);
+    }
+    return Node::UnaryExpr(UnaryExprNode { op, child: Box::new(child) });
+}
+
+fn alternation(alts: Vec<Node>) -> Node {
+    // Merge characters and classes into the first of them.
+    let mut ranges: Vec<(char, char)> = Vec::new();
+    let mut class_at: Option<usize> = None;
+    let mut rest: Vec<Node> = Vec::new();
+    for alt in alts {
+        match alt {
+            Node::Char(c) => ranges.push((c, c)),
+            Node::Class(r) => ranges.extend(r),
+            other => { rest.push(other); continue; }
+        }
+        class_at.get_or_insert(rest.len());
+    }
+    if let Some(idx) = class_at {
+        rest.insert(idx, class(normalize(ranges)));
+    }
+
+    let mut uniq: Vec<Node> = Vec::new();
+    for alt in rest {
+        if !uniq.contains(&alt) { uniq.push(alt); }
+    }
+    let keep: Vec<bool> = (0..uniq.len())
+        .map(|i| !(0..uniq.len()).any(|j| i != j && subsumes(&uniq[j], &uniq[i])))
+        .collect();
+    let uniq = uniq.into_iter()
+        .zip(keep)
+        .filter_map(|(alt, keep)| if keep { Some(alt) } else { None });
+
+    // Group alternatives by their first factor, in order of appearance.
+    let mut groups: Vec<Vec<Vec<Node>>> = Vec::new();
+    for alt in uniq {
+        let mut seq = Vec::new();
+        flatten(alt, Op::AND, &mut seq);
+        match groups.iter_mut().find(|g| g[0][0] == seq[0]) {
+            Some(group) => group.push(seq),
+            None => groups.push(vec![seq])
+        }
+    }
+    let nodes: Vec<Node> = groups.into_iter().map(factor).collect();
+    return join(nodes, Op::BAR);
+}
+
+fn factor(mut group: Vec<Vec<Node>>) -> Node {
+    if group.len() == 1 {
+        return join(group.pop().unwrap(), Op::AND);
+    }
+    let mut k = 1;
+    while group.iter().all(|seq| k < seq.len() && seq[k] == group[0][k]) {
+        k += 1;
+    }
+    let mut prefix: Vec<Node> = Vec::new();
+    let mut suffixes: Vec<Node> = Vec::new();
+    let mut optional = false;
+    for (i, mut seq) in group.into_iter().enumerate() {
+        let suffix = seq.split_off(k);
+        if i == 0 { prefix = seq; }
+        if suffix.is_empty() {
+            optional = true;
+        } else {
+            suffixes.push(join(suffix, Op::AND));
+        }
+    }
+    if !suffixes.is_empty() {
+        let mut rest = alternation(suffixes);
+        if optional { rest = quantify(Op::QUESTION, rest); }
+        prefix.push(rest);
+    }
+    return join(prefix, Op::AND);
+}
+
+// Whether every string matched by `a` is matched by `b`.
+fn subsumes(b: &Node, a: &Node) -> bool {
+    if let Node::UnaryExpr(u) = b {
+        if *u.child == *a { return true; }
+        if let Node::UnaryExpr(v) = a {
+            return u.op == Op::STAR && v.child == u.child;
+        }
+    }
+    return false;
+}
+
+fn flatten(node: Node, op: Op, out: &mut Vec<Node>) {
+    match node {
+        Node::BinaryExpr(n) if n.op == op => {
+            flatten(*n.left, op, out);
+            flatten(*n.right, op, out);
+        },
+        node => out.push(node)
+    }
+}
+
+fn join(nodes: Vec<Node>, op: Op) -> Node {
+    let mut nodes = nodes.into_iter();
+    let mut root = nodes.next().expect("Nothing to join");
+    for node in nodes {
+        root = Node::BinaryExpr(BinaryExprNode {
+            op, left: Box::new(root), right: Box::new(node)
+        });
+    }
+    return root;
+}
+
+fn class(ranges: Vec<(char, char)>) -> Node {
+    if let [(lo, hi)] = ranges[..] {
+        if lo == hi { return Node::Char(lo); }
+    }
+    return Node::Class(ranges);
+}
+
+/// Sorts `ranges`, dropping empty ones and merging any that overlap or touch.
+pub fn normalize(mut ranges: Vec<(char, char)>) -> Vec<(char, char)> {
+    ranges.retain(|(lo, hi)| lo <= hi);
+    ranges.sort();
+    let mut out: Vec<(char, char)> = Vec::new();
+    for (lo, hi) in ranges {
+        if let Some(last) = out.last_mut() {
+            if lo as u32 <= last.1 as u32 + 1 {
+                last.1 = last.1.max(hi);
+                continue;
+            }
+        }
+        out.push((lo, hi));
+    }
+    return out;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parser::parse_regex, printer};
+
+    fn check(before: &str, after: &str) {
+        check_node(parse_regex(before).expect("Valid regex"), after);
+    }
+
+    fn check_node(before: Node, after: &str) {
+        let shown = printer::regex(&before);
+        let out = printer::regex(&simplify(before));
+        assert!(out == after, "{shown} => {out}, expected {after}");
+    }
+
+    // Specs have no optional operator, but simplify both makes and
+    // meets it.
+    fn unary(op: Op, child: Node) -> Node {
+        return Node::UnaryExpr(UnaryExprNode { op, child: Box::new(child) });
+    }
+
+    #[test]
+    fn rewrites() {
+        check("a|a", "a");
+        check("a|b|c", "[a-c]");
+        check("a|[a-z]", "[a-z]");
+        check("[0-9]|x|[a-f]|y", "[0-9a-fx-y]");
+        check("(x*)*", "x*");
+        let x = || Node::Char('x');
+        check_node(unary(Op::QUESTION, unary(Op::QUESTION, unary(Op::QUESTION, x()))), "x?");
+        check_node(unary(Op::QUESTION, unary(Op::PLUS, x())), "x*");
+        check_node(unary(Op::PLUS, unary(Op::QUESTION, x())), "x*");
+        check("x|x*", "x*");
+        check("x+|x*", "x*");
+        check("ab|ac", "a[b-c]");
+        check("ab|a", "ab?");
+        check("abc|abd|e", "ab[c-d]|e");
+        check("(ab|ab)*c", "(ab)*c");
+        check("while|for|if|int", "while|for|i(f|nt)");
+    }
+
+    #[test]
+    fn keeps_captures() {
+        check("(?<a>x)y|(?<a>x)z", "(?<a>x)y|(?<a>x)z");
+        check("(?<a>x)|(?<a>x)", "(?<a>x)|(?<a>x)");
+        let root = simplify(parse_regex("(?<a>x)y|(?<a>x)z").unwrap());
+        assert!(root.groups() == ["a", "a"]);
+    }
+}
@@ -0,0 +1,104 @@
+use crate::simplify::normalize;
+use crate::unicode_tables::PROPERTIES;
+
+/// A Unicode property class, `\p{Name}` or its complement `\P{Name}`.
+/// Names are general categories, either two letters (`Lu`, `Nd`) or the
+/// one-letter group (`L`, `N`), or `XID_Start` and `XID_Continue` from
+/// UAX #31.
+#[derive(Copy, Clone, Debug, PartialEq, Eq)]
+pub struct Property {
+    name: &'static str,
+    pub negated: bool
+}
+
+// One-letter groups are the union of the categories they prefix.
+const GROUPS: [&str; 7] = ["C", "L", "M", "N", "P", "S", "Z"];
+
+impl Property {
+    pub fn lookup(name: &str, negated: bool) -> Option<Self> {
+        let name = GROUPS.iter().copied()
+            .chain(PROPERTIES.iter().map(|(n, _)| *n))
+            .find(|n| *n == name)?;
+        return Some(Property { name, negated });
+    }
+
+    pub fn name(&self) -> &'static str {
+        return self.name;
+    }
+
+    /// The characters in the class as sorted, disjoint ranges.
+    pub fn ranges(&self) -> Vec<(char, char)> {
+        let mut ranges: Vec<(char, char)> = Vec::new();
+        for (name, table) in PROPERTIES {
+            let grouped = self.name.len() == 1 && name.len() == 2 &&
+                name.starts_with(self.name);
+            if *name == self.name || grouped {
+                ranges.extend_from_slice(table);
+            }
+        }
+        let ranges = normalize(ranges);
+        if self.negated { return complement(&ranges); }
+        return ranges;
+    }
+}
+
+/// Every char not in `ranges`, which must be sorted and disjoint.
+pub fn complement(ranges: &[(char, char)]) -> Vec<(char, char)> {
+    let mut out: Vec<(char, char)> = Vec::new();
+    let mut next = 0u32;
+    for (lo, hi) in ranges {
+        push_gap(next, *lo as u32, &mut out);
+        next = *hi as u32 + 1;
+    }
+    push_gap(next, char::MAX as u32 + 1, &mut out);
+    return out;
+}
+
+// Adds `lo..end`, skipping the surrogates, which are not chars.
+fn push_gap(lo: u32, end: u32, out: &mut Vec<(char, char)>) {
+    let gaps = [(lo, end.min(0xD800)), (lo.max(0xE000), end)];
+    for (lo, end) in gaps {
+        if lo >= end { continue; }
+        let c = |n: u32| char::from_u32(n).expect("Surrogates are skipped");
+        out.push((c(lo), c(end - 1)));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn contains(ranges: &[(char, char)], c: char) -> bool {
+        return ranges.iter().any(|(lo, hi)| *lo <= c && c <= *hi);
+    }
+
+    #[test]
+    fn properties() {
+        assert!(Property::lookup("Latin", false).is_none());
+        let lu = Property::lookup("Lu", false).unwrap().ranges();
+        assert!(contains(&lu, 'A') && contains(&lu, 'Ω') && !contains(&lu, 'a'));
+
+        let letters = Property::lookup("L", false).unwrap().ranges();
+        for c in ['a', 'Z', 'λ', 'ж', '中', 'ª'] { assert!(contains(&letters, c), "{c}"); }
+        for c in ['1', '_', ' ', '→'] { assert!(!contains(&letters, c), "{c}"); }
+
+        let digits = Property::lookup("Nd", false).unwrap().ranges();
+        assert!(contains(&digits, '7') && contains(&digits, '٣') && !contains(&digits, 'Ⅷ'));
+
+        let start = Property::lookup("XID_Start", false).unwrap().ranges();
+        let cont = Property::lookup("XID_Continue", false).unwrap().ranges();
+        assert!(contains(&start, 'é') && !contains(&start, '_') && !contains(&start, '1'));
+        assert!(contains(&cont, '_') && contains(&cont, '1') && !contains(&cont, '-'));
+    }
+
+    #[test]
+    fn negation() {
+        let not_letters = Property::lookup("L", true).unwrap().ranges();
+        assert!(contains(&not_letters, '1') && !contains(&not_letters, 'a'));
+        assert!(contains(&not_letters, '\0') && contains(&not_letters, char::MAX));
+
+        assert!(complement(&[]) == vec![('\0', '\u{D7FF}'), ('\u{E000}', char::MAX)]);
+        assert!(complement(&[('\0', 'a'), ('c', char::MAX)]) == vec![('b', 'b')]);
+        assert!(complement(&complement(&[('x', 'z')])) == vec![('x', 'z')]);
+    }
+}
@@ -0,0 +1,88 @@
+/// Splits the scalar values `lo..=hi` into sequences of byte ranges, so
+/// that a string is in the range exactly when its UTF-8 encoding matches
+/// one of the sequences. Each sequence is as long as the encodings it
+/// covers, and the sequences come out in ascending order.
+/// e.g. U+0000-U+07FF => [00-7F], [C2-DF][80-BF].
+pub fn sequences(lo: char, hi: char) -> Vec<Vec<(u8, u8)>> {
+    let mut out: Vec<Vec<(u8, u8)>> = Vec::new();
+    if lo > hi { return out; }
+    let mut stack: Vec<(u32, u32)> = vec![(lo as u32, hi as u32)];
+    'ranges: while let Some((s, e)) = stack.pop() {
+        // Surrogates have no encoding.
+        if s < 0xD800 && e > 0xDFFF {
+            stack.push((0xE000, e));
+            stack.push((s, 0xD7FF));
+            continue;
+        }
+        // Both ends need encodings of the same length.
+        for max in [0x7F, 0x7FF, 0xFFFF] {
+            if s <= max && max < e {
+                stack.push((max + 1, e));
+                stack.push((s, max));
+                continue 'ranges;
+            }
+        }
+        if e <= 0x7F {
+            out.push(vec![(s as u8, e as u8)]);
+            continue;
+        }
+        // Split until each continuation byte spans a whole 80-BF block
+        // or the leading bytes agree.
+        for i in 1..4 {
+            let m: u32 = (1 << (6 * i)) - 1;
+            if s & !m != e & !m {
+                if s & m != 0 {
+                    stack.push(((s | m) + 1, e));
+                    stack.push((s, s | m));
+                    continue 'ranges;
+                }
+                if e & m != m {
+                    stack.push((e & !m, e));
+                    stack.push((s, (e & !m) - 1));
+                    continue 'ranges;
+                }
+            }
+        }
+        let (mut a, mut b) = ([0u8; 4], [0u8; 4]);
+        let a = encode(s, &mut a);
+        let b = encode(e, &mut b);
+        out.push(a.iter().zip(b.iter()).map(|(x, y)| (*x, *y)).collect());
+    }
+    return out;
+}
+
+fn encode(c: u32, buf: &mut [u8; 4]) -> &[u8] {
+    let c = char::from_u32(c).expect("Surrogates are split out");
+    return c.encode_utf8(buf).as_bytes();
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn matches(seqs: &[Vec<(u8, u8)>], c: char) -> bool {
+        let mut buf = [0u8; 4];
+        let bytes = c.encode_utf8(&mut buf).as_bytes();
+        return seqs.iter().any(|seq| seq.len() == bytes.len() &&
+            seq.iter().zip(bytes).all(|((lo, hi), b)| lo <= b && b <= hi));
+    }
+
+    #[test]
+    fn ranges() {
+        assert!(sequences('a', 'z') == vec![vec![(b'a', b'z')]]);
+        assert!(sequences('\0', '\u{7FF}') == vec![
+            vec![(0x00, 0x7F)],
+            vec![(0xC2, 0xDF), (0x80, 0xBF)]
+        ]);
+        assert!(sequences('λ', 'λ') == vec![vec![(0xCE, 0xCE), (0xBB, 0xBB)]]);
+        assert!(sequences('z', 'a').is_empty());
+
+        let cases = [('a', 'é'), ('α', 'ω'), ('\u{FF}', '\u{10FFFF}'), ('\u{D000}', '\u{E100}')];
+        for (lo, hi) in cases {
+            let seqs = sequences(lo, hi);
+            for c in ('\0'..='\u{10FFFF}').step_by(7).chain([lo, hi]) {
+                assert!(matches(&seqs, c) == (lo <= c && c <= hi), "{lo}-{hi}: {c}");
+            }
+        }
+    }
+}
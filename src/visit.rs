@@ -0,0 +1,147 @@
+use crate::ast::{Node, Match, BinaryExprNode, UnaryExprNode, CaptureNode};
+use crate::unicode::Property;
+
+/// Read-only traversal over a spec's AST. Every method defaults to
+/// walking its children, so an analysis only overrides what it needs
+/// and calls the matching `walk_*` function to keep descending.
+pub trait Visitor {
+    fn visit_match(&mut self, m: &Match) { walk_match(self, m); }
+    fn visit_node(&mut self, node: &Node) { walk_node(self, node); }
+    fn visit_binary(&mut self, node: &BinaryExprNode) { walk_binary(self, node); }
+    fn visit_unary(&mut self, node: &UnaryExprNode) { walk_unary(self, node); }
+    fn visit_capture(&mut self, node: &CaptureNode) { walk_capture(self, node); }
+    fn visit_class(&mut self, _ranges: &[(char, char)]) {}
+    fn visit_property(&mut self, _prop: Property) {}
+    fn visit_char(&mut self, _c: char) {}
+}
+
+pub fn walk_match<V: Visitor + ?Sized>(v: &mut V, m: &Match) {
+    v.visit_node(&m.root);
+}
+
+pub fn walk_node<V: Visitor + ?Sized>(v: &mut V, node: &Node) {
+    match node {
+        Node::Char(c) => v.visit_char(*c),
+        Node::Class(ranges) => v.visit_class(ranges),
+        Node::Property(p) => v.visit_property(*p),
+        Node::BinaryExpr(n) => v.visit_binary(n),
+        Node::UnaryExpr(n) => v.visit_unary(n),
+        Node::Capture(n) => v.visit_capture(n)
+    }
+}
+
+pub fn walk_binary<V: Visitor + ?Sized>(v: &mut V, node: &BinaryExprNode) {
+    v.visit_node(&node.left);
+    v.visit_node(&node.right);
+}
+
+pub fn walk_unary<V: Visitor + ?Sized>(v: &mut V, node: &UnaryExprNode) {
+    v.visit_node(&node.child);
+}
+
+pub fn walk_capture<V: Visitor + ?Sized>(v: &mut V, node: &CaptureNode) {
+    v.visit_node(&node.child);
+}
+
+/// Rewriting traversal. `visit_node` receives the slot holding each
+/// node, so a pass may replace whole subtrees as well as edit leaves.
+pub trait VisitorMut {
+    fn visit_match(&mut self, m: &mut Match) { walk_match_mut(self, m); }
+    fn visit_node(&mut self, node: &mut Node) { walk_node_mut(self, node); }
+    fn visit_binary(&mut self, node: &mut BinaryExprNode) { walk_binary_mut(self, node); }
+    fn visit_unary(&mut self, node: &mut UnaryExprNode) { walk_unary_mut(self, node); }
+    fn visit_capture(&mut self, node: &mut CaptureNode) { walk_capture_mut(self, node); }
+    fn visit_class(&mut self, _ranges: &mut Vec<(char, char)>) {}
+    fn visit_property(&mut self, _prop: &mut Property) {}
+    fn visit_char(&mut self, _c: &mut char) {}
+}
+
+pub fn walk_match_mut<V: VisitorMut + ?Sized>(v: &mut V, m: &mut Match) {
+    v.visit_node(&mut m.root);
+}
+
+pub fn walk_node_mut<V: VisitorMut + ?Sized>(v: &mut V, node: &mut Node) {
+    match node {
+        Node::Char(c) => v.visit_char(c),
+        Node::Class(ranges) => v.visit_class(ranges),
+        Node::Property(p) => v.visit_property(p),
+        Node::BinaryExpr(n) => v.visit_binary(n),
+        Node::UnaryExpr(n) => v.visit_unary(n),
+        Node::Capture(n) => v.visit_capture(n)
+    }
+}
+
+pub fn walk_binary_mut<V: VisitorMut + ?Sized>(v: &mut V, node: &mut BinaryExprNode) {
+    v.visit_node(&mut node.left);
+    v.visit_node(&mut node.right);
+}
+
+pub fn walk_unary_mut<V: VisitorMut + ?Sized>(v: &mut V, node: &mut UnaryExprNode) {
+    v.visit_node(&mut node.child);
+}
+
+pub fn walk_capture_mut<V: VisitorMut + ?Sized>(v: &mut V, node: &mut CaptureNode) {
+    v.visit_node(&mut node.child);
+}
+
+/// Makes every letter match both of its cases.
+pub struct CaseFold;
+
+impl VisitorMut for CaseFold {
+    fn visit_node(&mut self, node: &mut Node) {
+        walk_node_mut(self, node);
+        if let Node::Char(c) = *node {
+            if c.is_alphabetic() {
+                let mut ranges = vec![(c, c)];
+                self.visit_class(&mut ranges);
+                if ranges.len() > 1 { *node = Node::Class(ranges); }
+            }
+        }
+    }
+
+    fn visit_class(&mut self, ranges: &mut Vec<(char, char)>) {
+        let mut folded: Vec<(char, char)> = Vec::new();
+        for (lo, hi) in ranges.iter() {
+            for c in *lo..=*hi {
+                for f in c.to_lowercase().chain(c.to_uppercase()) {
+                    let inside = ranges.iter().any(|(l, h)| *l <= f && f <= *h);
+                    if !inside && !folded.contains(&(f, f)) {
+                        folded.push((f, f));
+                    }
+                }
+            }
+        }
+        ranges.append(&mut folded);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{parser::{parse_regex, parse_spec}, printer};
+
+    struct Counter { chars: usize, nodes: usize }
+    impl Visitor for Counter {
+        fn visit_node(&mut self, node: &Node) {
+            self.nodes += 1;
+            walk_node(self, node);
+        }
+        fn visit_char(&mut self, _c: char) { self.chars += 1; }
+    }
+
+    #[test]
+    fn count() {
+        let mut counter = Counter { chars: 0, nodes: 0 };
+        for m in parse_spec("\"ab*\" AB; \"(c|[d-f])+\" C;").unwrap() {
+            counter.visit_match(&m);
+        }
+        assert!(counter.chars == 3 && counter.nodes == 8);
+    }
+
+    #[test]
+    fn case_fold() {
+        let mut root = parse_regex("if[a-c_]*").unwrap();
+        CaseFold.visit_node(&mut root);
+        assert!(printer::regex(&root) == "[iI][fF][a-c_ABC]*");
+    }
+}
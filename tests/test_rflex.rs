@@ -1,38 +1,89 @@
-use std::{process::{Command, Stdio}, path::Path};
+use std::{process::{Command, Stdio}, path::Path, sync::Mutex};
+
+// Every test regenerates the tester's tokenizer.rs, so they take turns.
+static TESTER: Mutex<()> = Mutex::new(());
+
+fn generate_and_lex(genpath: &str, flags: &[&str], inpath: &str, outpath: &str) {
+    let _guard = TESTER.lock().unwrap_or_else(|e| e.into_inner());
+    let tokpath = "tests/tester/src/tokenizer.rs";
+    let out1 = Command::new("target/debug/rflex")
+        .args(flags)
+        .arg(genpath)
+        .arg(tokpath)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .output()
+        .expect("Failed to execute command");
+    println!("{}", String::from_utf8_lossy(&out1.stdout));
+    assert!(out1.status.success(), "Generator Failed");
+
+    let out2 = Command::new("cargo")
+        .arg("run")
+        .arg(inpath)
+        .arg(outpath)
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .current_dir("tests/tester")
+        .output()
+        .expect("Failed to execute command");        
+    println!("{}", String::from_utf8_lossy(&out2.stdout));
+    assert!(out2.status.success(), "Parsing Failed");
+}
 
 #[test]
 fn test_parse() {
     let mut i = 0;
     while Path::new(&format!("tests/tester/data/gen-{i}.tk")).exists() {
-        let tokpath = "tests/tester/src/tokenizer.rs";
-        let genpath = &format!("tests/tester/data/gen-{i}.tk");
-        let out1 = Command::new("target/debug/rflex")
-            .arg(genpath)
-            .arg(tokpath)
-            .stdout(Stdio::piped())
-            .stderr(Stdio::inherit())
-            .output()
-            .expect("Failed to execute command");
-        println!("{}", String::from_utf8_lossy(&out1.stdout));
-        assert!(out1.status.success(), "Generator Failed");
-        
-        let inpath = &format!("data/in-{i}.txt");
-        let outpath = &format!("data/out-{i}.txt");
-        let out2 = Command::new("cargo")
-            .arg("run")
-            .arg(inpath)
-            .arg(outpath)
-            .stdout(Stdio::piped())
-            .stderr(Stdio::inherit())
-            .current_dir("tests/tester")
-            .output()
-            .expect("Failed to execute command");        
-        println!("{}", String::from_utf8_lossy(&out2.stdout));
-        if out2.status.success() {
-            assert!(0 == out2.status.code().unwrap());
-        } else {
-            assert!(false, "Parsing Failed");
-        }
+        generate_and_lex(
+            &format!("tests/tester/data/gen-{i}.tk"), &[],
+            &format!("data/in-{i}.txt"), &format!("data/out-{i}.txt")
+        );
+        i += 1;
+    }
+}
+
+#[test]
+fn test_binary() {
+    let mut i = 0;
+    while Path::new(&format!("tests/tester/data/bin-{i}.tk")).exists() {
+        generate_and_lex(
+            &format!("tests/tester/data/bin-{i}.tk"), &["--binary"],
+            &format!("data/bin-in-{i}.txt"), &format!("data/bin-out-{i}.txt")
+        );
         i += 1;
     }
 }
+
+#[test]
+fn test_whitespace() {
+    let mut i = 0;
+    while Path::new(&format!("tests/tester/data/ws-{i}.tk")).exists() {
+        generate_and_lex(
+            &format!("tests/tester/data/ws-{i}.tk"), &[],
+            &format!("data/ws-in-{i}.txt"), &format!("data/ws-out-{i}.txt")
+        );
+        i += 1;
+    }
+}
+
+#[test]
+fn test_constructions() {
+    for flag in ["--construction=direct", "--construction=derivative"] {
+        let mut i = 0;
+        while Path::new(&format!("tests/tester/data/gen-{i}.tk")).exists() {
+            generate_and_lex(
+                &format!("tests/tester/data/gen-{i}.tk"), &[flag],
+                &format!("data/in-{i}.txt"), &format!("data/out-{i}.txt")
+            );
+            i += 1;
+        }
+        let mut i = 0;
+        while Path::new(&format!("tests/tester/data/ws-{i}.tk")).exists() {
+            generate_and_lex(
+                &format!("tests/tester/data/ws-{i}.tk"), &[flag],
+                &format!("data/ws-in-{i}.txt"), &format!("data/ws-out-{i}.txt")
+            );
+            i += 1;
+        }
+    }
+}
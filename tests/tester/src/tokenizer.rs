@@ -2,63 +2,107 @@ use std::fs;
 use Token::*;
 #[derive(Debug, PartialEq, Eq)]
 pub enum Token {
-	IF(String),
-	ELSE(String),
-	INT(String),
-	FLOAT(String),
-	STRING(String),
-	PLUS(String),
-	MINUS(String),
-	MULTIPLY(String),
-	DIVIDE(String),
-	ASSIGN(String),
-	EQUALS(String),
-	NOT_EQUALS(String),
-	GREATER_THAN(String),
-	LESS_THAN(String),
-	GREATER_THAN_OR_EQUAL(String),
-	LESS_THAN_OR_EQUAL(String),
-	LBRACE(String),
-	RBRACE(String),
-	LBRACKET(String),
-	RBRACKET(String),
-	LPAR(String),
-	RPAR(String),
-	TRUE(String),
-	FALSE(String),
+	LAMBDA(String),
+	ARROW(String),
 	IDENT(String),
-	NUMBER(String),
-	SEMI(String),
+	STRING(String),
 	EOF
 }
+// CLASSES[b] is the class of byte b; bytes in a class move every state alike.
+const CLASSES: [u8; 256] = [
+      0,   0,   0,   0,   0,   0,   0,   0,   0,   1,   1,   2,   2,   2,   2,   2,
+      2,   2,   2,   2,   2,   2,   2,   2,   2,   2,   2,   2,   2,   2,   2,   2,
+      3,   4,   4,   4,   4,   4,   4,   5,   6,   6,   6,   6,   6,   6,   6,   6,
+      6,   6,   6,   6,   6,   6,   6,   6,   6,   6,   6,   6,   6,   6,   6,   6,
+      6,   7,   7,   7,   7,   7,   7,   7,   7,   7,   7,   7,   7,   7,   7,   7,
+      7,   7,   7,   7,   7,   7,   7,   7,   7,   7,   7,   8,   8,   8,   8,   8,
+      8,   9,   9,   9,   9,   9,   9,   9,   9,   9,   9,   9,   9,   9,   9,   9,
+      9,   9,   9,   9,   9,   9,   9,   9,   9,   9,   9,  10,  10,  10,  10,  10,
+     11,  11,  11,  11,  11,  11,  12,  13,  13,  13,  14,  14,  14,  14,  14,  14,
+     15,  15,  16,  17,  17,  17,  17,  17,  17,  17,  17,  17,  17,  17,  17,  17,
+     18,  18,  18,  18,  18,  18,  18,  18,  18,  18,  18,  18,  18,  18,  18,  18,
+     19,  20,  20,  20,  20,  20,  20,  21,  22,  22,  22,  23,  24,  24,  24,  24,
+     25,  25,  25,  26,  27,  27,  27,  27,  27,  27,  27,  27,  27,  27,  28,  29,
+     30,  31,  32,  32,  32,  32,  32,  32,  32,  32,  32,  32,  32,  32,  32,  32,
+     32,  32,  33,  34,  34,  34,  34,  34,  34,  34,  34,  34,  34,  34,  34,  34,
+     34,  34,  34,  34,  34,  34,  34,  34,  34,  34,  34,  34,  34,  34,  34,  34,
+];
+// JUMPS[s][k] is the state s moves to on a byte of class k.
+const JUMPS: [[u16; 35]; 17] = [
+    [1, 0, 1, 0, 1, 2, 1, 3, 1, 3, 1, 1, 1, 1, 1, 1,
+     1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 4, 1, 5, 6, 1, 1,
+     1, 7, 1],
+    [1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1,
+     1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1,
+     1, 1, 1],
+    [1, 1, 1, 2, 1, 12, 1, 2, 1, 2, 1, 1, 1, 1, 1, 1,
+     1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 13, 14, 15, 16,
+     1, 1, 1],
+    [1, 1, 1, 1, 1, 1, 1, 3, 1, 3, 1, 1, 1, 1, 1, 1,
+     1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 4, 1, 11, 6, 1, 1,
+     1, 1, 1],
+    [1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1,
+     1, 1, 3, 3, 3, 1, 3, 3, 3, 1, 1, 1, 1, 1, 1, 1,
+     1, 1, 1],
+    [1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1,
+     1, 1, 1, 1, 3, 3, 3, 10, 3, 1, 1, 1, 1, 1, 1, 1,
+     1, 1, 1],
+    [1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 3, 3, 3, 1, 1,
+     1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1,
+     1, 1, 1],
+    [1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 8, 1, 1, 1,
+     1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1,
+     1, 1, 1],
+    [1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1,
+     9, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1,
+     1, 1, 1],
+    [1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1,
+     1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1,
+     1, 1, 1],
+    [1, 1, 1, 1, 1, 1, 1, 3, 1, 3, 1, 1, 1, 1, 1, 1,
+     1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 4, 1, 11, 6, 1, 1,
+     1, 1, 1],
+    [1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1,
+     1, 1, 1, 1, 3, 3, 3, 3, 3, 1, 1, 1, 1, 1, 1, 1,
+     1, 1, 1],
+    [1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1,
+     1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1,
+     1, 1, 1],
+    [1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1,
+     1, 1, 1, 1, 2, 2, 2, 2, 2, 1, 1, 1, 1, 1, 1, 1,
+     1, 1, 1],
+    [1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 2, 2, 2, 1, 1,
+     1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1,
+     1, 1, 1],
+    [1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1,
+     1, 1, 1, 2, 2, 2, 2, 2, 2, 1, 1, 1, 1, 1, 1, 1,
+     1, 1, 1],
+    [1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 2, 2, 2, 2, 1,
+     1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1, 1,
+     1, 1, 1],
+];
+const DEAD: usize = 1;
 #[derive(Debug, PartialEq, Eq)]
 pub struct TokenErr {
    pub error: String
 }
 pub struct Lexer {
-  chars:   Vec<char>,
+  chars:   Vec<u8>,
   pos:     usize,
   begins:  Vec<usize>,
   tabs:    Vec<usize>,
   column:  usize,
-  accepts: [usize; 50]
+  accepts: [usize; 17]
 }
 impl Lexer {
     pub fn new(fname: &str) -> Result<Self, Box<dyn std::error::Error>> {
         let chars = fs::read_to_string(fname)?
-            .chars()
-            .collect();
+            .into_bytes();
 		let accepts = [
-			   0, 			   0, 			   0, 			  21, 			  22,
-			   8, 			   6, 			   7, 			   9, 			  26,
-			  27, 			  14, 			  10, 			  13, 			  25,
-			  19, 			  20, 			  25, 			  25, 			  25,
-			  25, 			  25, 			  17, 			  18, 			  25,
-			  25, 			  23, 			  25, 			  25, 			  25,
-			  25, 			   5, 			   1, 			  25, 			   3,
-			  25, 			  25, 			  25, 			  25, 			   4,
-			  25, 			  25, 			  24, 			  25, 			  25,
-			   2, 			  15, 			  11, 			  16, 			  12,
+			   0, 			   0, 			   0, 			   3, 			   0,
+			   0, 			   0, 			   0, 			   0, 			   2,
+			   1, 			   0, 			   4, 			   0, 			   0,
+			   0, 			   0
 		];
         return Ok(Lexer { 
            chars,
@@ -70,18 +114,18 @@ impl Lexer {
         });
     }
 
-   fn advance(&mut self) -> char {
+   fn advance(&mut self) -> u8 {
        let c = self.chars[self.pos];
         match c {
-           '\n' => {
+           b'\n' => {
                self.column = 0;
                self.begins.push(self.pos + 1);
            },
-           '\t' => {
+           b'\t' => {
                self.tabs.push(self.column);
                self.column += 4 - (self.column % 4);
            }
-           _ => self.column += 1
+           _ => if c & 0xC0 != 0x80 { self.column += 1 }
        }
        self.pos += 1;
        return c;
@@ -90,343 +134,43 @@ impl Lexer {
        self.pos -= 1;
        let c = self.chars[self.pos];
        match c {
-           '\n' => {
+           b'\n' => {
                self.begins.pop();
                self.column = self.pos - self.begins[self.begins.len() - 1];
            }
-           '\t' => {
+           b'\t' => {
                self.column = self.tabs.pop().unwrap();
            }
-           _ => self.column -= 1
+           _ => if c & 0xC0 != 0x80 { self.column -= 1 }
        }
    }
 	pub fn next(&mut self) -> Result<Token, TokenErr> {
 		if self.pos == self.chars.len() { return Ok(EOF); }
 		let mut stk: Vec<usize> = Vec::new();
-		let mut chars: Vec<char> = Vec::new();
+		let mut chars: Vec<u8> = Vec::new();
 		let mut state: usize = 0;
 		loop {
-			if self.pos == self.chars.len() { break; }
-			let c = self.advance();
-			state = match state {
-				0 => match c {
-					'\t' => continue,
-					'\n' => continue,
-					'\r' => continue,
-					' ' => continue,
-					'!' => 2,
-					'(' => 3,
-					')' => 4,
-					'*' => 5,
-					'+' => 6,
-					'-' => 7,
-					'/' => 8,
-					'0'..='9' => 9,
-					';' => 10,
-					'<' => 11,
-					'=' => 12,
-					'>' => 13,
-					'A'..='Z' => 14,
-					'[' => 15,
-					']' => 16,
-					'a'..='d' => 14,
-					'e' => 17,
-					'f' => 18,
-					'g' | 'h' => 14,
-					'i' => 19,
-					'j'..='r' => 14,
-					's' => 20,
-					't' => 21,
-					'u'..='z' => 14,
-					'{' => 22,
-					'}' => 23,
-					_ => 1
-				},
-				1 => {
-					stk.push(state);
-					chars.push(c);
-					break;
-				}
-				2 => match c {
-					'=' => 49,
-					_ => 1
-				},
-				3 => match c {
-					_ => 1
-				},
-				4 => match c {
-					_ => 1
-				},
-				5 => match c {
-					_ => 1
-				},
-				6 => match c {
-					_ => 1
-				},
-				7 => match c {
-					_ => 1
-				},
-				8 => match c {
-					_ => 1
-				},
-				9 => match c {
-					'0'..='9' => 9,
-					_ => 1
-				},
-				10 => match c {
-					_ => 1
-				},
-				11 => match c {
-					'=' => 48,
-					_ => 1
-				},
-				12 => match c {
-					'=' => 47,
-					_ => 1
-				},
-				13 => match c {
-					'=' => 46,
-					_ => 1
-				},
-				14 => match c {
-					'0'..='9' => 14,
-					'A'..='Z' => 14,
-					'a'..='z' => 14,
-					_ => 1
-				},
-				15 => match c {
-					_ => 1
-				},
-				16 => match c {
-					_ => 1
-				},
-				17 => match c {
-					'0'..='9' => 14,
-					'A'..='Z' => 14,
-					'a'..='k' => 14,
-					'l' => 43,
-					'm'..='z' => 14,
-					_ => 1
-				},
-				18 => match c {
-					'0'..='9' => 14,
-					'A'..='Z' => 14,
-					'a' => 35,
-					'b'..='k' => 14,
-					'l' => 36,
-					'm'..='z' => 14,
-					_ => 1
-				},
-				19 => match c {
-					'0'..='9' => 14,
-					'A'..='Z' => 14,
-					'a'..='e' => 14,
-					'f' => 32,
-					'g'..='m' => 14,
-					'n' => 33,
-					'o'..='z' => 14,
-					_ => 1
-				},
-				20 => match c {
-					'0'..='9' => 14,
-					'A'..='Z' => 14,
-					'a'..='s' => 14,
-					't' => 27,
-					'u'..='z' => 14,
-					_ => 1
-				},
-				21 => match c {
-					'0'..='9' => 14,
-					'A'..='Z' => 14,
-					'a'..='q' => 14,
-					'r' => 24,
-					's'..='z' => 14,
-					_ => 1
-				},
-				22 => match c {
-					_ => 1
-				},
-				23 => match c {
-					_ => 1
-				},
-				24 => match c {
-					'0'..='9' => 14,
-					'A'..='Z' => 14,
-					'a'..='t' => 14,
-					'u' => 25,
-					'v'..='z' => 14,
-					_ => 1
-				},
-				25 => match c {
-					'0'..='9' => 14,
-					'A'..='Z' => 14,
-					'a'..='d' => 14,
-					'e' => 26,
-					'f'..='z' => 14,
-					_ => 1
-				},
-				26 => match c {
-					'0'..='9' => 14,
-					'A'..='Z' => 14,
-					'a'..='z' => 14,
-					_ => 1
-				},
-				27 => match c {
-					'0'..='9' => 14,
-					'A'..='Z' => 14,
-					'a'..='q' => 14,
-					'r' => 28,
-					's'..='z' => 14,
-					_ => 1
-				},
-				28 => match c {
-					'0'..='9' => 14,
-					'A'..='Z' => 14,
-					'a'..='h' => 14,
-					'i' => 29,
-					'j'..='z' => 14,
-					_ => 1
-				},
-				29 => match c {
-					'0'..='9' => 14,
-					'A'..='Z' => 14,
-					'a'..='m' => 14,
-					'n' => 30,
-					'o'..='z' => 14,
-					_ => 1
-				},
-				30 => match c {
-					'0'..='9' => 14,
-					'A'..='Z' => 14,
-					'a'..='f' => 14,
-					'g' => 31,
-					'h'..='z' => 14,
-					_ => 1
-				},
-				31 => match c {
-					'0'..='9' => 14,
-					'A'..='Z' => 14,
-					'a'..='z' => 14,
-					_ => 1
-				},
-				32 => match c {
-					'0'..='9' => 14,
-					'A'..='Z' => 14,
-					'a'..='z' => 14,
-					_ => 1
-				},
-				33 => match c {
-					'0'..='9' => 14,
-					'A'..='Z' => 14,
-					'a'..='s' => 14,
-					't' => 34,
-					'u'..='z' => 14,
-					_ => 1
-				},
-				34 => match c {
-					'0'..='9' => 14,
-					'A'..='Z' => 14,
-					'a'..='z' => 14,
-					_ => 1
-				},
-				35 => match c {
-					'0'..='9' => 14,
-					'A'..='Z' => 14,
-					'a'..='k' => 14,
-					'l' => 40,
-					'm'..='z' => 14,
-					_ => 1
-				},
-				36 => match c {
-					'0'..='9' => 14,
-					'A'..='Z' => 14,
-					'a'..='n' => 14,
-					'o' => 37,
-					'p'..='z' => 14,
-					_ => 1
-				},
-				37 => match c {
-					'0'..='9' => 14,
-					'A'..='Z' => 14,
-					'a' => 38,
-					'b'..='z' => 14,
-					_ => 1
-				},
-				38 => match c {
-					'0'..='9' => 14,
-					'A'..='Z' => 14,
-					'a'..='s' => 14,
-					't' => 39,
-					'u'..='z' => 14,
-					_ => 1
-				},
-				39 => match c {
-					'0'..='9' => 14,
-					'A'..='Z' => 14,
-					'a'..='z' => 14,
-					_ => 1
-				},
-				40 => match c {
-					'0'..='9' => 14,
-					'A'..='Z' => 14,
-					'a'..='r' => 14,
-					's' => 41,
-					't'..='z' => 14,
-					_ => 1
-				},
-				41 => match c {
-					'0'..='9' => 14,
-					'A'..='Z' => 14,
-					'a'..='d' => 14,
-					'e' => 42,
-					'f'..='z' => 14,
-					_ => 1
-				},
-				42 => match c {
-					'0'..='9' => 14,
-					'A'..='Z' => 14,
-					'a'..='z' => 14,
-					_ => 1
-				},
-				43 => match c {
-					'0'..='9' => 14,
-					'A'..='Z' => 14,
-					'a'..='r' => 14,
-					's' => 44,
-					't'..='z' => 14,
-					_ => 1
-				},
-				44 => match c {
-					'0'..='9' => 14,
-					'A'..='Z' => 14,
-					'a'..='d' => 14,
-					'e' => 45,
-					'f'..='z' => 14,
-					_ => 1
-				},
-				45 => match c {
-					'0'..='9' => 14,
-					'A'..='Z' => 14,
-					'a'..='z' => 14,
-					_ => 1
-				},
-				46 => match c {
-					_ => 1
-				},
-				47 => match c {
-					_ => 1
-				},
-				48 => match c {
-					_ => 1
-				},
-				49 => match c {
-					_ => 1
-				},
-				_ => panic!("Invalid State!")
-			};
-			stk.push(state);
-			chars.push(c);
+		    if self.pos == self.chars.len() { break; }
+		    let c = self.advance();
+		    let next = JUMPS[state][CLASSES[c as usize] as usize] as usize;
+		    // Only whitespace leads back to the start, so all read so far is skipped.
+		    if next == 0 {
+		        state = 0;
+		        stk.clear();
+		        chars.clear();
+		        continue;
+		    }
+		    // No token goes on from here, so c is not part of one.
+		    if next == DEAD {
+		        self.retract();
+		        break;
+		    }
+		    state = next;
+		    stk.push(state);
+		    chars.push(c);
 		}
+		// Only whitespace was left.
+		if stk.len() == 0 && self.pos == self.chars.len() { return Ok(EOF); }
 		while stk.len() > 0 &&
 		   self.accepts[stk[stk.len() - 1]] == 0 {
 		   stk.pop().unwrap();
@@ -435,45 +179,23 @@ impl Lexer {
 		}
 		if stk.len() == 0 {
 		    let start = self.begins[self.begins.len() - 1];
-		    let error_line: String = self.chars[start..]
+		    let error_line: Vec<u8> = self.chars[start..]
 		        .iter()
-		        .take_while(|&&c| c != '\n')
+		        .take_while(|&&c| c != b'\n')
+		        .cloned()
 		        .collect();
 		    return Err(TokenErr{error: format!(
 		        "Failed to lex from: \n{}\n{}^",
-		        error_line,
+		        String::from_utf8_lossy(&error_line),
 		        " ".repeat(self.column)
 		    )});
 		}
-		let word : String = chars.iter().collect();
+		let word : String = String::from_utf8_lossy(&chars).into_owned();
 		match self.accepts[stk[stk.len() - 1]] {
-			1    => return Ok(IF(word)),
-			2    => return Ok(ELSE(word)),
-			3    => return Ok(INT(word)),
-			4    => return Ok(FLOAT(word)),
-			5    => return Ok(STRING(word)),
-			6    => return Ok(PLUS(word)),
-			7    => return Ok(MINUS(word)),
-			8    => return Ok(MULTIPLY(word)),
-			9    => return Ok(DIVIDE(word)),
-			10   => return Ok(ASSIGN(word)),
-			11   => return Ok(EQUALS(word)),
-			12   => return Ok(NOT_EQUALS(word)),
-			13   => return Ok(GREATER_THAN(word)),
-			14   => return Ok(LESS_THAN(word)),
-			15   => return Ok(GREATER_THAN_OR_EQUAL(word)),
-			16   => return Ok(LESS_THAN_OR_EQUAL(word)),
-			17   => return Ok(LBRACE(word)),
-			18   => return Ok(RBRACE(word)),
-			19   => return Ok(LBRACKET(word)),
-			20   => return Ok(RBRACKET(word)),
-			21   => return Ok(LPAR(word)),
-			22   => return Ok(RPAR(word)),
-			23   => return Ok(TRUE(word)),
-			24   => return Ok(FALSE(word)),
-			25   => return Ok(IDENT(word)),
-			26   => return Ok(NUMBER(word)),
-			27   => return Ok(SEMI(word)),
+			1    => return Ok(LAMBDA(word)),
+			2    => return Ok(ARROW(word)),
+			3    => return Ok(IDENT(word)),
+			4    => return Ok(STRING(word)),
 			_    => panic!("Invalid Accepting State")
 		}
 	}